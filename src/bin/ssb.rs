@@ -1,50 +1,311 @@
 use anyhow::{Result, anyhow};
-use chrono::{DateTime, Local};
-use port_check::free_local_port_in_range;
+use clap::{Args, Parser, Subcommand};
+use simple_site_builder::throttle::ThrottleRule;
 use simple_site_builder::*;
+use std::net::{IpAddr, TcpListener};
+use std::num::NonZeroU64;
 use std::path::PathBuf;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
 use tower_livereload::LiveReloadLayer;
-use tracing::info;
 use tracing::metadata::LevelFilter;
+use tracing::{error, info};
+
+#[derive(Debug, Parser)]
+#[command(
+  version,
+  about = "Simple Site Builder",
+  args_conflicts_with_subcommands = true
+)]
+struct Cli {
+  #[command(subcommand)]
+  command: Option<Command>,
+
+  #[command(flatten)]
+  serve: ServeArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+  /// Build the site, watch for changes, and run
+  /// the preview server (the default)
+  Serve(ServeArgs),
+}
+
+#[derive(Debug, Args)]
+struct ServeArgs {
+  /// Config file to load
+  #[arg(long, default_value = "ssb.toml")]
+  config: PathBuf,
+
+  /// Address for the preview server (use 0.0.0.0
+  /// to preview from other devices on the LAN)
+  #[arg(long)]
+  bind: Option<IpAddr>,
+
+  /// Port for the preview server. Fails if the
+  /// port is already in use
+  #[arg(long, conflicts_with = "port_range")]
+  port: Option<u16>,
+
+  /// Range of ports to pick a free one from
+  /// (e.g. 5444-6000)
+  #[arg(long, value_parser = parse_port_range)]
+  port_range: Option<(u16, u16)>,
+
+  /// Don't open a browser when the server starts
+  #[arg(long)]
+  no_open: bool,
+
+  /// Serve over HTTPS with a locally generated
+  /// self-signed certificate
+  #[arg(long)]
+  https: bool,
+
+  /// Certificate (PEM) to use for HTTPS instead
+  /// of the generated one
+  #[arg(long, requires = "key")]
+  cert: Option<PathBuf>,
+
+  /// Private key (PEM) that goes with --cert
+  #[arg(long, requires = "cert")]
+  key: Option<PathBuf>,
+
+  /// Mirror scrolling and navigation between all
+  /// the browsers that have the site open
+  #[arg(long)]
+  sync: bool,
+
+  /// Milliseconds to wait before answering each
+  /// request. The throttle flags replace the
+  /// throttle rules in the config file
+  #[arg(long)]
+  latency: Option<u64>,
+
+  /// Bytes per second to send responses at
+  #[arg(long)]
+  bandwidth: Option<u64>,
+
+  /// Fraction of requests (0.0 to 1.0) that get a
+  /// 500 error
+  #[arg(long)]
+  error_rate: Option<f64>,
+
+  /// Print a one-time link that lets another
+  /// device view the preview
+  #[arg(long)]
+  auth_token: bool,
+
+  /// Show each request in the terminal as well as
+  /// the log files
+  #[arg(long)]
+  access_log: bool,
+
+  /// List the files in folders that don't have an
+  /// index.html
+  #[arg(long)]
+  listings: bool,
+
+  /// Path the site is deployed under (e.g.
+  /// /project). Links get it added and the preview
+  /// is served under it
+  #[arg(long)]
+  base_path: Option<String>,
+
+  /// Check for changes every INTERVAL
+  /// milliseconds (500 by default) instead of
+  /// using file notifications. For mounted volumes
+  /// where notifications don't arrive
+  #[arg(
+    long,
+    value_name = "INTERVAL",
+    num_args = 0..=1,
+    require_equals = true,
+    default_missing_value = "500"
+  )]
+  poll: Option<NonZeroU64>,
+}
+
+impl ServeArgs {
+  // Command line flags take precedence over
+  // the config file.
+  fn apply(
+    &self,
+    config: &mut Config,
+  ) {
+    if let Some(bind) = self.bind {
+      config.bind = bind;
+    }
+    if let Some(port) = self.port {
+      config.port = Some(port);
+    }
+    if let Some(port_range) = self.port_range {
+      config.port = None;
+      config.port_range = port_range;
+    }
+    if self.no_open {
+      config.open_browser = false;
+    }
+    if self.https {
+      config.https = true;
+    }
+    if let (Some(cert), Some(key)) =
+      (&self.cert, &self.key)
+    {
+      config.https = true;
+      config.tls_cert = Some(cert.clone());
+      config.tls_key = Some(key.clone());
+    }
+    if self.sync {
+      config.sync = true;
+    }
+    if self.auth_token {
+      config.auth_token = true;
+    }
+    if self.access_log {
+      config.access_log_stdout = true;
+    }
+    if self.listings {
+      config.directory_listings = true;
+    }
+    if let Some(base_path) = &self.base_path {
+      config.base_path = base_path.clone();
+    }
+    if let Some(poll) = self.poll {
+      config.poll = Some(poll);
+    }
+    let throttle = ThrottleRule {
+      latency: self.latency,
+      bandwidth: self.bandwidth,
+      error_rate: self.error_rate,
+      ..ThrottleRule::default()
+    };
+    // Replaces the config's rules since those
+    // with a path would otherwise take precedence.
+    if !throttle.is_empty() {
+      config.throttle = vec![throttle];
+    }
+  }
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-  let config = Config::new(
-    PathBuf::from("content"),
-    PathBuf::from("logs"),
-    PathBuf::from("docs"),
-    true,
-  );
+  let cli = Cli::parse();
+  let args = match cli.command {
+    Some(Command::Serve(args)) => args,
+    None => cli.serve,
+  };
+  let mut config = Config::load(&args.config)?;
+  args.apply(&mut config);
+
+  let status = BuildStatus::shared();
 
   let _log_guards = Logger::setup()
     .with_stdout(LevelFilter::INFO)
     .to_json_dir(&config.json_logs(), LevelFilter::INFO)
     .to_txt_dir(&config.txt_logs(), LevelFilter::INFO)
+    .with_status(status.clone())
+    .with_access_log_stdout(config.access_log_stdout)
     .init();
 
   info!("Initilizing");
 
-  let port = find_port()?;
+  let mut port = find_port(&config)?;
   info!("Found port for web server: {}", port);
 
   let live_reload = LiveReloadLayer::new();
   let reloader = live_reload.reloader();
-  let (tx, rx) = mpsc::channel::<DateTime<Local>>(32);
+  let (tx, rx) = mpsc::channel::<Rebuild>(32);
+  let (config_tx, mut config_rx) = mpsc::channel::<()>(8);
+  let (first_build_tx, first_build_rx) =
+    oneshot::channel();
 
-  let server = Server::new(config.clone(), port);
-  let server_handle = tokio::spawn(async move {
-    let _ = server.start(live_reload).await;
-  });
+  let client_events = client::client_events();
+
+  let server = Server::new(
+    config.clone(),
+    port,
+    status.clone(),
+    client_events.clone(),
+  );
+  let mut server_handle = start_server(
+    server,
+    live_reload.clone(),
+    first_build_rx,
+  );
 
-  let mut builder =
-    Builder::new(config.clone(), reloader, rx, port);
+  let mut builder = Builder::new(
+    config.clone(),
+    reloader,
+    rx,
+    port,
+    first_build_tx,
+    status.clone(),
+    client_events.clone(),
+  );
   let builder_handle = tokio::spawn(async move {
     let _ = builder.start().await;
   });
 
-  let watcher = Watcher::new(config.clone(), tx);
-  let _ = watcher.start().await;
+  let watcher = Watcher::new(
+    &config,
+    &args.config,
+    tx.clone(),
+    config_tx,
+  )?;
+  let watching = watcher.start();
+  tokio::pin!(watching);
+  loop {
+    tokio::select! {
+      _ = &mut watching => break,
+      Some(()) = config_rx.recv() => {
+        let mut new_config =
+          match Config::load(&args.config) {
+            Ok(new_config) => new_config,
+            Err(e) => {
+              error!("{:#}", e);
+              continue;
+            }
+          };
+        args.apply(&mut new_config);
+        info!("Reloaded {}", args.config.display());
+        watcher.watch(&new_config);
+        // Restarting the server drops the live
+        // reload and sync connections so it's only
+        // done when a server setting changed.
+        if new_config.server_changed(&config) {
+          // Stopped first so its port is free
+          server_handle.abort();
+          let _ = (&mut server_handle).await;
+          if new_config.address_changed(&config) {
+            match find_port(&new_config) {
+              Ok(new_port) => port = new_port,
+              Err(e) => error!(
+                "{:#}. Staying on port {}",
+                e, port
+              ),
+            }
+          }
+          info!("Restarting web server");
+          let server = Server::new(
+            new_config.clone(),
+            port,
+            status.clone(),
+            client_events.clone(),
+          );
+          server_handle = start_server(
+            server,
+            live_reload.clone(),
+            oneshot::channel().1,
+          );
+        }
+        config = new_config;
+        let rebuild =
+          Rebuild::Config(Box::new(config.clone()), port);
+        let _ = tx.send(rebuild).await;
+      }
+    }
+  }
 
   server_handle.abort();
   builder_handle.abort();
@@ -52,7 +313,92 @@ async fn main() -> Result<()> {
   Ok(())
 }
 
-fn find_port() -> Result<u16> {
-  free_local_port_in_range(5444..=6000)
-    .ok_or(anyhow!("Could not find port"))
+fn start_server(
+  server: Server,
+  live_reload: LiveReloadLayer,
+  first_build: oneshot::Receiver<()>,
+) -> JoinHandle<()> {
+  tokio::spawn(async move {
+    if let Err(e) =
+      server.start(live_reload, first_build).await
+    {
+      error!("{:#}", e);
+    }
+  })
+}
+
+fn find_port(config: &Config) -> Result<u16> {
+  match config.port {
+    Some(port) => {
+      if port_is_free(config.bind, port) {
+        Ok(port)
+      } else {
+        Err(anyhow!(
+          "Port {} is already in use on {}",
+          port,
+          config.bind
+        ))
+      }
+    }
+    None => {
+      let (start, end) = config.port_range;
+      (start..=end)
+        .find(|port| port_is_free(config.bind, *port))
+        .ok_or(anyhow!(
+          "Could not find a free port between {} and {}",
+          start,
+          end
+        ))
+    }
+  }
+}
+
+fn port_is_free(
+  bind: IpAddr,
+  port: u16,
+) -> bool {
+  TcpListener::bind((bind, port)).is_ok()
+}
+
+fn parse_port_range(value: &str) -> Result<(u16, u16)> {
+  let (start, end) = value.split_once('-').ok_or(
+    anyhow!("expected START-END (e.g. 5444-6000)"),
+  )?;
+  let (start, end) =
+    (start.parse::<u16>()?, end.parse::<u16>()?);
+  if start > end {
+    return Err(anyhow!(
+      "{} is greater than {}",
+      start,
+      end
+    ));
+  }
+  Ok((start, end))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn apply_throttle_test() {
+    let mut config = Config {
+      throttle: vec![ThrottleRule {
+        path: Some("/images/*".to_string()),
+        latency: Some(2000),
+        ..ThrottleRule::default()
+      }],
+      ..Config::default()
+    };
+    let cli = Cli::parse_from(["ssb", "--latency", "50"]);
+    cli.serve.apply(&mut config);
+    assert_eq!(
+      vec![ThrottleRule {
+        latency: Some(50),
+        ..ThrottleRule::default()
+      }],
+      config.throttle
+    );
+  }
 }
@@ -2,58 +2,199 @@ pub mod utils;
 
 use self::utils::*;
 use crate::config::Config;
+use crate::mock_api::MockApi;
+use crate::netlify::NetlifyRules;
+use crate::server::client::{ClientEvent, ClientEvents};
+use crate::server::forms::site_forms;
+use crate::server::server_urls;
+use crate::status::{
+  BUILD_SPAN, RouteInfo, SharedStatus,
+};
 use anyhow::Result;
 use chrono::{DateTime, Local};
+use globset::GlobBuilder;
 use markdown::{CompileOptions, Options};
 use minijinja::Value;
 use minijinja::context;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::sync::mpsc::Receiver;
+use tokio::sync::oneshot;
 use tower_livereload::Reloader;
-use tracing::info;
+use tracing::{error, info, info_span, warn};
+use walkdir::WalkDir;
+
+/// What the builder is asked to rebuild for.
+#[derive(Clone, Debug)]
+pub enum Rebuild {
+  /// Files changed at this time
+  Changed(DateTime<Local>),
+  /// The config file was loaded again. The port
+  /// is the one the server is on with it.
+  Config(Box<Config>, u16),
+}
 
 pub struct Builder {
   pub config: Config,
   pub reloader: Reloader,
-  pub rx: Receiver<DateTime<Local>>,
+  pub rx: Receiver<Rebuild>,
   pub port: u16,
+  pub first_build: Option<oneshot::Sender<()>>,
+  pub status: SharedStatus,
+  pub client_events: ClientEvents,
+  pub output_hashes: OutputHashes,
 }
 
 impl Builder {
   pub fn new(
     config: Config,
     reloader: Reloader,
-    rx: Receiver<DateTime<Local>>,
+    rx: Receiver<Rebuild>,
     port: u16,
+    first_build: oneshot::Sender<()>,
+    status: SharedStatus,
+    client_events: ClientEvents,
   ) -> Builder {
     Builder {
       config,
       reloader,
       rx,
       port,
+      first_build: Some(first_build),
+      status,
+      client_events,
+      output_hashes: OutputHashes::new(),
     }
   }
 
-  pub fn build_site(&self) -> Result<()> {
+  pub fn build_site(&mut self) -> Result<()> {
     let _ = clearscreen::clear();
+    self.report_not_found();
+    // Errors logged in this span are the ones
+    // reported for the build.
+    let _build = info_span!(BUILD_SPAN).entered();
+    self.status.lock().unwrap().start_build();
     info!("Building site");
     let _ = self.empty_dir();
-    let file_list = file_list(&self.config.content_root);
-    let _ = &self.transform_html(&file_list)?;
+    let ignore = IgnoreRules::new(&self.config);
+    let file_list = file_list(
+      &self.config.content_root,
+      &ignore,
+      &error_pages(&self.config),
+    );
+    let _ = &self.transform_html(&file_list, &ignore)?;
     let _ = &self.copy_files(&file_list)?;
-    info!(
-      "Reloading browser for: http://localhost:{}/",
-      self.port
+    if self.config.precompress {
+      self.precompress_files();
+    }
+    self.update_forms();
+    self.update_netlify_rules();
+    self.update_mock_api(&ignore);
+    let changed = self.changed_outputs();
+    self.status.lock().unwrap().finish_build();
+    self.update_browser(&changed);
+    Ok(())
+  }
+
+  // Lists the paths that 404ed since the last
+  // build started so they're easy to spot after
+  // the screen is cleared.
+  pub fn report_not_found(&self) {
+    let not_found = {
+      let status = self.status.lock().unwrap();
+      status.not_found_since(status.started)
+    };
+    if not_found.is_empty() {
+      return;
+    }
+    warn!(
+      "{} missing path(s) requested since the last build:",
+      not_found.len()
     );
+    not_found.iter().for_each(|(path, not_found)| {
+      match &not_found.referer {
+        Some(referer) => warn!(
+          "  {} ({} requests, linked from {})",
+          path, not_found.count, referer
+        ),
+        None => {
+          warn!(
+            "  {} ({} requests)",
+            path, not_found.count
+          )
+        }
+      }
+    });
+  }
+
+  // Compares the output folder to the one from
+  // the last build.
+  pub fn changed_outputs(&mut self) -> Vec<String> {
+    let hashes = output_hashes(&self.config.output_root);
+    let changed =
+      changed_outputs(&self.output_hashes, &hashes);
+    self.output_hashes = hashes;
+    self.status.lock().unwrap().changed_outputs =
+      changed.clone();
+    changed
+  }
+
+  // Stylesheet changes are swapped into open pages
+  // so they keep their scroll position and form
+  // state. Pages whose own HTML changed reload.
+  // Changes to anything else reload every page.
+  pub fn update_browser(
+    &self,
+    changed: &[String],
+  ) {
+    let url = &server_urls(
+      self.config.bind,
+      self.port,
+      self.config.https,
+    )[0];
+    let (stylesheets, others): (Vec<_>, Vec<_>) = changed
+      .iter()
+      .cloned()
+      .partition(|path| path.ends_with(".css"));
+    let (pages, others): (Vec<_>, Vec<_>) = others
+      .into_iter()
+      .partition(|path| path.ends_with(".html"));
+    // Browsers see the paths under the base path.
+    let base_path = self.config.base_prefix();
+    let with_base = |paths: Vec<String>| {
+      paths
+        .iter()
+        .map(|path| with_base_path(&base_path, path))
+        .collect::<Vec<_>>()
+    };
+    let (stylesheets, pages) =
+      (with_base(stylesheets), with_base(pages));
+    if changed.is_empty() {
+      info!("No changes to send to: {}", url);
+      return;
+    }
+    let event = if others.is_empty() {
+      info!("Sending changes to: {}", url);
+      ClientEvent::Update { stylesheets, pages }
+    } else {
+      info!("Reloading browser for: {}", url);
+      ClientEvent::Reload
+    };
+    // Pages ask for the changes once live reload
+    // tells them there are some.
+    self.client_events.lock().unwrap().push(event);
     let _ = &self.reloader.reload();
-    Ok(())
   }
 
   pub fn copy_files(
     &self,
     file_list: &[FileDetails],
   ) -> Result<()> {
+    // Stylesheets get the base path added to their
+    // URLs. Everything else is copied as is.
+    let base_path = self.config.base_prefix();
     file_list.iter().for_each(|details| {
       if details.file_move_type == FileMoveType::Copy {
         let input_path = &self
@@ -66,13 +207,145 @@ impl Builder {
           .output_root
           .join(details.output_folder.as_ref().unwrap())
           .join(details.output_name.as_ref().unwrap());
-        let _ =
-          copy_file_with_mkdir(input_path, output_path);
+        let is_css = details
+          .name
+          .extension()
+          .is_some_and(|extension| extension == "css");
+        if base_path.is_empty() || !is_css {
+          let _ =
+            copy_file_with_mkdir(input_path, output_path);
+        } else if let Ok(css) =
+          fs::read_to_string(input_path)
+        {
+          let _ = write_file_with_mkdir(
+            output_path,
+            &add_base_path_css(&base_path, &css),
+          );
+        }
       }
     });
     Ok(())
   }
 
+  pub fn precompress_files(&self) {
+    WalkDir::new(&self.config.output_root)
+      .into_iter()
+      .filter_map(|entry| entry.ok())
+      .filter(|entry| {
+        entry.path().extension().is_some_and(
+          |extension| {
+            PRECOMPRESS_EXTENSIONS
+              .contains(&extension.to_str().unwrap_or(""))
+          },
+        ) && entry.metadata().is_ok_and(|metadata| {
+          metadata.is_file()
+            && metadata.len()
+              >= self.config.precompress_min_size
+        })
+      })
+      .for_each(|entry| {
+        if let Err(e) = precompress_file(entry.path()) {
+          error!(
+            "Could not compress {}: {}",
+            entry.path().display(),
+            e
+          );
+        }
+      });
+  }
+
+  // Keeps what the introspection endpoints show
+  // in step with the latest build.
+  pub fn update_status(
+    &self,
+    file_list: &[FileDetails],
+    site_context: &Value,
+  ) {
+    let routes = file_list
+      .iter()
+      .map(|details| RouteInfo {
+        url: details.url(),
+        source: details
+          .folder
+          .join(&details.name)
+          .display()
+          .to_string(),
+        output: details
+          .output_folder
+          .as_ref()
+          .zip(details.output_name.as_ref())
+          .map(|(folder, name)| {
+            folder.join(name).display().to_string()
+          }),
+        kind: format!("{:?}", details.file_move_type),
+      })
+      .collect::<Vec<_>>();
+    let pages = file_list
+      .iter()
+      .filter(|details| {
+        details.file_move_type
+          == FileMoveType::TransformHtml
+      })
+      .map(|details| {
+        (
+          details
+            .folder
+            .join(&details.name)
+            .display()
+            .to_string(),
+          Value::from_serialize(details),
+        )
+      })
+      .collect();
+    let mut status = self.status.lock().unwrap();
+    status.routes = routes;
+    status.pages = pages;
+    status.site_context = Some(site_context.clone());
+  }
+
+  // Scanned once per build so form posts don't
+  // have to read every page.
+  pub fn update_forms(&self) {
+    let forms = site_forms(
+      &self.config.output_root,
+      &self.config.base_prefix(),
+    );
+    self.status.lock().unwrap().forms = forms;
+  }
+
+  // Loaded once per build so mock API requests
+  // don't have to set them up again.
+  pub fn update_mock_api(
+    &self,
+    ignore: &IgnoreRules,
+  ) {
+    let api = MockApi::load(
+      &self
+        .config
+        .content_root
+        .join(&self.config.api_dir),
+      &self.config.api_prefix,
+    );
+    api.errors.iter().for_each(|e| error!("{}", e));
+    let env = get_env(&self.config, ignore);
+    let mut status = self.status.lock().unwrap();
+    status.mock_api = api;
+    status.mock_env = Some(Arc::new(env));
+  }
+
+  // Loaded from the output once per build so the
+  // preview server doesn't parse them for every
+  // request.
+  pub fn update_netlify_rules(&self) {
+    let rules = NetlifyRules::load(
+      &self.config.netlify_toml,
+      &self.config.output_root,
+    );
+    rules.errors.iter().for_each(|e| error!("{}", e));
+    self.status.lock().unwrap().netlify_rules =
+      Arc::new(rules);
+  }
+
   // TODO: set this up so the names aren't the same
   pub fn empty_dir(&self) -> Result<()> {
     let _ = empty_dir(&self.config.output_root);
@@ -83,9 +356,7 @@ impl Builder {
     &self,
     file_list: &[FileDetails],
   ) -> Value {
-    let mut highlights: BTreeMap<String, String> =
-      BTreeMap::new();
-    file_list
+    let keys = file_list
       .iter()
       .filter(|details| {
         details.extension == Some("css".to_string())
@@ -95,72 +366,263 @@ impl Builder {
           || details.extension == Some("py".to_string())
           || details.extension == Some("rs".to_string())
       })
-      .for_each(|details| {
-        let content_path = self
-          .config
-          .content_root
-          .join(&details.folder)
-          .join(&details.name);
-        let key_path = details.folder.join(&details.name);
-        let content =
-          fs::read_to_string(content_path).unwrap();
-        let highlighted = highlight_code(
-          &content,
-          details.extension.as_ref().unwrap().as_str(),
-        );
-        highlights.insert(
-          key_path.display().to_string(),
-          highlighted,
+      .map(|details| {
+        details
+          .folder
+          .join(&details.name)
+          .display()
+          .to_string()
+      })
+      .collect();
+    Value::from_object(LazyFiles::new(
+      &self.config.content_root,
+      keys,
+      Builder::highlight_file,
+    ))
+  }
+
+  pub fn highlight_file(
+    input_path: &Path
+  ) -> Option<Value> {
+    match fs::read_to_string(input_path) {
+      Ok(content) => Some(Value::from(highlight_code(
+        &content,
+        input_path.extension()?.to_str()?,
+      ))),
+      Err(e) => {
+        error!(
+          "Could not read {}: {}",
+          input_path.display(),
+          e
         );
-      });
-    Value::from_serialize(highlights)
+        None
+      }
+    }
   }
 
   pub fn load_data(
     &self,
     file_list: &[FileDetails],
   ) -> Value {
-    let mut data_map: BTreeMap<String, Value> =
-      BTreeMap::new();
-
-    file_list
+    let keys: BTreeSet<String> = file_list
       .iter()
       .filter(|details| {
-        details.extension == Some("json".to_string())
+        let is_data = details
+          .extension
+          .as_ref()
+          .is_some_and(|extension| {
+            DATA_EXTENSIONS.contains(&extension.as_str())
+          });
+        let is_schema = details
+          .name
+          .display()
+          .to_string()
+          .ends_with(".schema.json");
+        // Mock API files are templates, not data
+        let is_mock_api = details
+          .folder
+          .starts_with(&self.config.api_dir);
+        is_data && !is_schema && !is_mock_api
       })
-      .for_each(|details| {
-        let key = details.folder.join(&details.name);
-        let input_path =
-          self.config.content_root.join(&key);
-        match fs::read_to_string(&input_path) {
-          Ok(json) => {
-            match serde_json::from_str::<Value>(&json) {
-              Ok(data) => {
-                data_map.insert(
-                  key.display().to_string(),
-                  data,
-                );
-              }
-              Err(e) => {
-                // TODO: Add better error handling here
-                dbg!(e);
-              }
-            }
-          }
+      .map(|details| {
+        details
+          .folder
+          .join(&details.name)
+          .display()
+          .to_string()
+      })
+      .collect();
+    let checked =
+      Builder::checked_data(&self.config, &keys);
+    let infer_csv_types = self.config.csv_infer_types;
+    Value::from_object(
+      LazyFiles::new(
+        &self.config.content_root,
+        keys,
+        move |input_path| {
+          Builder::load_data_file(
+            input_path,
+            infer_csv_types,
+          )
+        },
+      )
+      .with_loaded(checked),
+    )
+  }
+
+  /// Loads and checks every data file that has a
+  /// schema so violations are reported even when
+  /// no page reads the file. Files without one are
+  /// left to load when a template asks for them.
+  pub fn checked_data(
+    config: &Config,
+    keys: &BTreeSet<String>,
+  ) -> BTreeMap<String, Option<Value>> {
+    keys
+      .iter()
+      .filter(|key| {
+        Builder::schema_for(config, key).is_some()
+      })
+      .map(|key| {
+        let data = Builder::load_data_file(
+          &config.content_root.join(key),
+          config.csv_infer_types,
+        )
+        .and_then(|data| {
+          Builder::check_data(config, key, data)
+        });
+        (key.clone(), data)
+      })
+      .collect()
+  }
+
+  pub fn load_data_file(
+    input_path: &Path,
+    infer_csv_types: bool,
+  ) -> Option<Value> {
+    let extension = input_path.extension()?.to_str()?;
+    match fs::read_to_string(input_path) {
+      Ok(text) => {
+        match parse_data(
+          &text,
+          extension,
+          infer_csv_types,
+        ) {
+          Ok(data) => Some(data),
           Err(e) => {
-            // TODO: Add better error messaging here
-            dbg!(e);
+            error!(
+              "Could not parse {}: {}",
+              input_path.display(),
+              e
+            );
+            None
           }
         }
-      });
+      }
+      Err(e) => {
+        error!(
+          "Could not read {}: {}",
+          input_path.display(),
+          e
+        );
+        None
+      }
+    }
+  }
+
+  /// Checks a data file against its schema if it
+  /// has one. A schema can sit next to the data
+  /// file (`sample.csv.schema.json` for
+  /// `sample.csv` or `sample.schema.json` for
+  /// `sample.json`) or be mapped to a glob of data
+  /// files in the config. Violations are build
+  /// errors and the file's data is left out.
+  pub fn check_data(
+    config: &Config,
+    key: &str,
+    data: Value,
+  ) -> Option<Value> {
+    let Some(schema_path) =
+      Builder::schema_for(config, key)
+    else {
+      return Some(data);
+    };
+    let schema = match Builder::load_schema(&schema_path)
+    {
+      Ok(schema) => schema,
+      Err(e) => {
+        error!(
+          "Could not load schema {}: {}",
+          schema_path.display(),
+          e
+        );
+        return None;
+      }
+    };
+    let json = match serde_json::to_value(&data) {
+      Ok(json) => json,
+      Err(e) => {
+        error!("Could not convert {}: {}", key, e);
+        return None;
+      }
+    };
+    match validate_data(&schema, &json) {
+      Ok(violations) if violations.is_empty() => {
+        Some(data)
+      }
+      Ok(violations) => {
+        violations.iter().for_each(|violation| {
+          error!(
+            "{} does not match {}: {}",
+            key,
+            schema_path.display(),
+            violation
+          );
+        });
+        None
+      }
+      Err(e) => {
+        error!("{}: {}", schema_path.display(), e);
+        None
+      }
+    }
+  }
+
+  pub fn load_schema(
+    schema_path: &Path
+  ) -> Result<serde_json::Value> {
+    let text = fs::read_to_string(schema_path)?;
+    Ok(serde_json::from_str(&text)?)
+  }
 
-    Value::from_serialize(data_map)
+  pub fn schema_for(
+    config: &Config,
+    key: &str,
+  ) -> Option<PathBuf> {
+    let mapped = config.schemas.iter().find_map(
+      |(pattern, schema)| {
+        GlobBuilder::new(pattern)
+          .literal_separator(true)
+          .build()
+          .ok()?
+          .compile_matcher()
+          .is_match(key)
+          .then(|| config.content_root.join(schema))
+      },
+    );
+    if mapped.is_some() {
+      return mapped;
+    }
+    let key = Path::new(key);
+    let full_name = PathBuf::from(format!(
+      "{}.schema.json",
+      key.display()
+    ));
+    let siblings = if key
+      .extension()
+      .is_some_and(|extension| extension == "json")
+    {
+      vec![full_name, key.with_extension("schema.json")]
+    } else {
+      vec![full_name]
+    };
+    siblings
+      .into_iter()
+      .map(|sibling| config.content_root.join(sibling))
+      .find(|sibling| sibling.is_file())
   }
 
   pub async fn start(&mut self) -> Result<()> {
     info!("Starting builder");
     let _ = &self.build_site();
-    while (self.rx.recv().await).is_some() {
+    if let Some(first_build) = self.first_build.take() {
+      let _ = first_build.send(());
+    }
+    while let Some(rebuild) = self.rx.recv().await {
+      if let Rebuild::Config(config, port) = rebuild {
+        self.config = *config;
+        self.port = port;
+      }
       let _ = &self.build_site();
     }
     Ok(())
@@ -170,58 +632,86 @@ impl Builder {
     &self,
     file_list: &[FileDetails],
   ) -> Value {
-    let mut markdown_map: BTreeMap<String, String> =
-      BTreeMap::new();
-    file_list
+    let keys = file_list
       .iter()
       .filter(|details| {
         details.extension == Some("md".to_string())
       })
-      .for_each(|details| {
-        let content_path = self
-          .config
-          .content_root
-          .join(&details.folder)
-          .join(&details.name);
-        let key_path = details.folder.join(&details.name);
-        let md_content =
-          fs::read_to_string(content_path).unwrap();
-        match markdown::to_html_with_options(
-          &md_content,
-          &Options {
-            compile: CompileOptions {
-              allow_dangerous_html: true,
-              ..CompileOptions::default()
-            },
-            ..Options::default()
-          },
-        ) {
-          Ok(parsed) => {
-            markdown_map.insert(
-              key_path.display().to_string(),
-              parsed,
-            );
-          }
-          Err(e) => {
-            dbg!(e);
-          }
-        }
-      });
-    Value::from_serialize(markdown_map)
+      .map(|details| {
+        details
+          .folder
+          .join(&details.name)
+          .display()
+          .to_string()
+      })
+      .collect();
+    Value::from_object(LazyFiles::new(
+      &self.config.content_root,
+      keys,
+      Builder::load_markdown_file,
+    ))
+  }
+
+  pub fn load_markdown_file(
+    input_path: &Path
+  ) -> Option<Value> {
+    let md_content = match fs::read_to_string(input_path)
+    {
+      Ok(md_content) => md_content,
+      Err(e) => {
+        error!(
+          "Could not read {}: {}",
+          input_path.display(),
+          e
+        );
+        return None;
+      }
+    };
+    match markdown::to_html_with_options(
+      &md_content,
+      &Options {
+        compile: CompileOptions {
+          allow_dangerous_html: true,
+          ..CompileOptions::default()
+        },
+        ..Options::default()
+      },
+    ) {
+      Ok(parsed) => Some(Value::from(parsed)),
+      Err(e) => {
+        error!(
+          "Could not parse {}: {}",
+          input_path.display(),
+          e
+        );
+        None
+      }
+    }
   }
 
   pub fn transform_html(
     &self,
     file_list: &[FileDetails],
+    ignore: &IgnoreRules,
   ) -> Result<()> {
-    let folders = folder_list(&self.config.content_root);
-    let env = get_env(&self.config.content_root);
+    let folders =
+      folder_list(&self.config.content_root, ignore);
+    let env = get_env(&self.config, ignore);
     let file_list_as_value =
       Value::from_serialize(file_list);
     let folders_as_value = Value::from_serialize(folders);
     let markdown_files = self.load_markdown(file_list);
     let highlighted = self.highlight_files(file_list);
     let data = self.load_data(file_list);
+    let base_path = self.config.base_prefix();
+    let site_context = context!(
+      data => data,
+      files => file_list_as_value,
+      folders => folders_as_value,
+      highlight => highlighted,
+      markdown => markdown_files,
+    );
+    self.update_status(file_list, &site_context);
     file_list.iter().for_each(|details| {
       if details.file_move_type
         == FileMoveType::TransformHtml
@@ -240,25 +730,21 @@ impl Builder {
         );
         match env.get_template(&template_name) {
           Ok(template) => match template.render(context!(
-            data => data,
-            files => file_list_as_value,
-            folders => folders_as_value,
-            highlight => highlighted,
-            markdown => markdown_files,
             file => Value::from_serialize(details),
+            ..site_context.clone()
           )) {
             Ok(content) => {
               let _ = write_file_with_mkdir(
                 output_path,
-                &content,
+                &add_base_path_html(&base_path, &content),
               );
             }
             Err(e) => {
-              println!("{}", e);
+              error!("{}: {}", template_name, e);
             }
           },
           Err(e) => {
-            dbg!(e);
+            error!("{}: {}", template_name, e);
           }
         }
       }
@@ -268,3 +754,97 @@ impl Builder {
 
   //
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case(
+    "examples/data/sample.json",
+    Some("content/examples/data/sample.schema.json")
+  )]
+  #[case("examples/data/sample.csv", None)]
+  #[case("examples/data/sample.toml", None)]
+  #[case("examples/data/sample-2.json", None)]
+  fn schema_for_test(
+    #[case] key: &str,
+    #[case] expected: Option<&str>,
+  ) {
+    assert_eq!(
+      expected.map(PathBuf::from),
+      Builder::schema_for(&Config::default(), key)
+    );
+  }
+
+  #[test]
+  fn schema_for_mapped_test() {
+    let mut config = Config::default();
+    config.schemas.insert(
+      "examples/data/*.csv".to_string(),
+      PathBuf::from("examples/data/sample.schema.json"),
+    );
+    assert_eq!(
+      Some(PathBuf::from(
+        "content/examples/data/sample.schema.json"
+      )),
+      Builder::schema_for(
+        &config,
+        "examples/data/sample.csv"
+      )
+    );
+  }
+
+  #[rstest]
+  #[case(r#"{"dataset": {"alfa": "fox"}}"#, true)]
+  #[case(r#"{"dataset": {"alfa": 1}}"#, false)]
+  #[case(r#"{"other": {}}"#, false)]
+  fn check_data_test(
+    #[case] json: &str,
+    #[case] expected: bool,
+  ) {
+    let data: Value =
+      serde_json::from_str::<serde_json::Value>(json)
+        .map(Value::from_serialize)
+        .unwrap();
+    assert_eq!(
+      expected,
+      Builder::check_data(
+        &Config::default(),
+        "examples/data/sample.json",
+        data
+      )
+      .is_some()
+    );
+  }
+
+  #[test]
+  fn checked_data_test() {
+    let mut config = Config::default();
+    config.schemas.insert(
+      "examples/data/*.csv".to_string(),
+      PathBuf::from("examples/data/sample.schema.json"),
+    );
+    let keys = [
+      "examples/data/sample.csv",
+      "examples/data/sample.json",
+      "examples/data/sample.toml",
+    ]
+    .into_iter()
+    .map(|key| key.to_string())
+    .collect();
+    let got = Builder::checked_data(&config, &keys)
+      .into_iter()
+      .map(|(key, data)| (key, data.is_some()))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      vec![
+        ("examples/data/sample.csv".to_string(), false),
+        ("examples/data/sample.json".to_string(), true),
+      ],
+      got
+    );
+  }
+}
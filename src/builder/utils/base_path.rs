@@ -0,0 +1,185 @@
+use regex::{Captures, Regex};
+
+/// Adds the base path to a root-relative URL.
+/// Protocol-relative (`//`), relative and absolute
+/// URLs are left alone as are ones that already
+/// start with the base path.
+pub fn with_base_path(
+  base_path: &str,
+  url: &str,
+) -> String {
+  let has_base_path =
+    url.strip_prefix(base_path).is_some_and(|rest| {
+      rest.is_empty() || rest.starts_with(['/', '?', '#'])
+    });
+  if base_path.is_empty()
+    || !url.starts_with('/')
+    || url.starts_with("//")
+    || has_base_path
+  {
+    url.to_string()
+  } else {
+    format!("{}{}", base_path, url)
+  }
+}
+
+/// Adds the base path to the root-relative URLs in
+/// a page's link, source and form attributes,
+/// `srcset` lists and any inline CSS.
+pub fn add_base_path_html(
+  base_path: &str,
+  html: &str,
+) -> String {
+  if base_path.is_empty() {
+    return html.to_string();
+  }
+  let attribute = Regex::new(
+    r#"(?i)(\s(?:href|src|action|formaction|poster|data)\s*=\s*["']?)(/[^"'\s>]*)"#,
+  )
+  .unwrap();
+  let srcset = Regex::new(
+    r#"(?i)(\s(?:srcset|imagesrcset)\s*=\s*["'])([^"']*)"#,
+  )
+  .unwrap();
+  let html =
+    attribute.replace_all(html, |caps: &Captures| {
+      format!(
+        "{}{}",
+        &caps[1],
+        with_base_path(base_path, &caps[2])
+      )
+    });
+  let html =
+    srcset.replace_all(&html, |caps: &Captures| {
+      let candidates = caps[2]
+        .split(',')
+        .map(|candidate| {
+          let url = candidate.trim_start();
+          format!(
+            "{}{}",
+            &candidate[..candidate.len() - url.len()],
+            with_base_path(base_path, url)
+          )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+      format!("{}{}", &caps[1], candidates)
+    });
+  add_base_path_css(base_path, &html)
+}
+
+/// Adds the base path to the root-relative URLs in
+/// `url()` values and `@import`s.
+pub fn add_base_path_css(
+  base_path: &str,
+  css: &str,
+) -> String {
+  if base_path.is_empty() {
+    return css.to_string();
+  }
+  let url =
+    Regex::new(r#"(url\(\s*["']?)(/[^"'\s)]*)"#).unwrap();
+  let import =
+    Regex::new(r#"(@import\s+["'])(/[^"']*)"#).unwrap();
+  let replace = |caps: &Captures| {
+    format!(
+      "{}{}",
+      &caps[1],
+      with_base_path(base_path, &caps[2])
+    )
+  };
+  let css = url.replace_all(css, replace);
+  import.replace_all(&css, replace).to_string()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("/project", "/", "/project/")]
+  #[case("/project", "/about/", "/project/about/")]
+  #[case(
+    "/project",
+    "/project/about/",
+    "/project/about/"
+  )]
+  #[case("/project", "/projects/", "/project/projects/")]
+  #[case(
+    "/project",
+    "//cdn.example.com/a.js",
+    "//cdn.example.com/a.js"
+  )]
+  #[case(
+    "/project",
+    "https://example.com/",
+    "https://example.com/"
+  )]
+  #[case("/project", "about/", "about/")]
+  #[case("/project", "#top", "#top")]
+  #[case("", "/about/", "/about/")]
+  fn with_base_path_test(
+    #[case] base_path: &str,
+    #[case] url: &str,
+    #[case] expected: &str,
+  ) {
+    assert_eq!(expected, with_base_path(base_path, url));
+  }
+
+  #[rstest]
+  #[case(
+    r#"<a href="/about/">About</a>"#,
+    r#"<a href="/project/about/">About</a>"#
+  )]
+  #[case(
+    r#"<img src='/images/a.png' alt="">"#,
+    r#"<img src='/project/images/a.png' alt="">"#
+  )]
+  #[case(
+    r#"<form method="post" action=/contact>"#,
+    r#"<form method="post" action=/project/contact>"#
+  )]
+  #[case(
+    r#"<img srcset="/a.png 1x, /b.png 2x, c.png 3x">"#,
+    r#"<img srcset="/project/a.png 1x, /project/b.png 2x, c.png 3x">"#
+  )]
+  #[case(
+    r#"<div style="background: url('/a.png')">"#,
+    r#"<div style="background: url('/project/a.png')">"#
+  )]
+  #[case(
+    r#"<script src="//cdn.example.com/a.js"></script>"#,
+    r#"<script src="//cdn.example.com/a.js"></script>"#
+  )]
+  #[case(
+    r#"<a data-href="/about/">/about/</a>"#,
+    r#"<a data-href="/about/">/about/</a>"#
+  )]
+  fn add_base_path_html_test(
+    #[case] html: &str,
+    #[case] expected: &str,
+  ) {
+    assert_eq!(
+      expected,
+      add_base_path_html("/project", html)
+    );
+  }
+
+  #[test]
+  fn add_base_path_css_test() {
+    let css = r#"@import "/styles/base.css";
+@font-face { src: url(/fonts/a.woff2); }
+body { background: url("/images/a.png"); }
+.logo { background: url(logo.png); }"#;
+    let expected = r#"@import "/project/styles/base.css";
+@font-face { src: url(/project/fonts/a.woff2); }
+body { background: url("/project/images/a.png"); }
+.logo { background: url(logo.png); }"#;
+    assert_eq!(
+      expected,
+      add_base_path_css("/project", css)
+    );
+  }
+}
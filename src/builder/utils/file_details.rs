@@ -1,7 +1,30 @@
+use crate::config::Config;
 use serde::Serialize;
 use std::path::Path;
 use std::path::PathBuf;
 
+/// The page hosts show for server errors
+pub const SERVER_ERROR_PAGE: &str = "500.html";
+
+/// Error pages keep their file names instead of
+/// being moved into a folder so hosts like Netlify
+/// and GitHub Pages can find them. They're the
+/// configured `not_found_page` and `500.html`.
+pub fn error_pages(config: &Config) -> Vec<PathBuf> {
+  let mut pages = vec![
+    config.not_found_page.clone(),
+    PathBuf::from(SERVER_ERROR_PAGE),
+  ];
+  pages.dedup();
+  pages
+}
+
+/// Netlify config files at the root of the content
+/// folder are published even though they start
+/// with an "_".
+pub const NETLIFY_FILES: &[&str] =
+  &["_headers", "_redirects"];
+
 #[derive(Debug, PartialEq, Serialize)]
 pub enum FileMoveType {
   Copy,
@@ -20,15 +43,22 @@ pub struct FileDetails {
 }
 
 impl FileDetails {
-  pub fn new(input_path: &Path) -> FileDetails {
+  pub fn new(
+    input_path: &Path,
+    error_pages: &[PathBuf],
+  ) -> FileDetails {
     let extension =
       FileDetails::get_extension(input_path);
     let folder = FileDetails::get_input_dir(input_path);
     let name = FileDetails::get_input_name(input_path);
-    let output_folder =
-      FileDetails::get_output_dir(input_path);
-    let output_name =
-      FileDetails::get_output_name(input_path);
+    let output_folder = FileDetails::get_output_dir(
+      input_path,
+      error_pages,
+    );
+    let output_name = FileDetails::get_output_name(
+      input_path,
+      error_pages,
+    );
     let file_move_type =
       FileDetails::get_file_move_type(input_path);
     FileDetails {
@@ -60,7 +90,9 @@ impl FileDetails {
   pub fn get_file_move_type(
     input_path: &Path
   ) -> FileMoveType {
-    if input_path
+    if FileDetails::is_netlify_file(input_path) {
+      FileMoveType::Copy
+    } else if input_path
       .iter()
       .any(|part| part.to_str().unwrap().starts_with("_"))
     {
@@ -80,13 +112,21 @@ impl FileDetails {
   }
 
   pub fn get_output_dir(
-    input_path: &Path
+    input_path: &Path,
+    error_pages: &[PathBuf],
   ) -> Option<PathBuf> {
-    if input_path
+    if FileDetails::is_netlify_file(input_path) {
+      Some(PathBuf::from(""))
+    } else if input_path
       .iter()
       .any(|part| part.to_str().unwrap().starts_with("_"))
     {
       None
+    } else if FileDetails::is_error_page(
+      input_path,
+      error_pages,
+    ) {
+      Some(FileDetails::get_input_dir(input_path))
     } else {
       let file_stem =
         input_path.file_stem().unwrap().to_str().unwrap();
@@ -117,29 +157,63 @@ impl FileDetails {
   }
 
   pub fn get_output_name(
-    input_path: &Path
+    input_path: &Path,
+    error_pages: &[PathBuf],
   ) -> Option<PathBuf> {
-    if input_path
+    if FileDetails::is_netlify_file(input_path) {
+      Some(input_path.to_path_buf())
+    } else if input_path
       .iter()
       .any(|part| part.to_str().unwrap().starts_with("_"))
     {
       None
+    } else if FileDetails::is_error_page(
+      input_path,
+      error_pages,
+    ) {
+      Some(FileDetails::get_input_name(input_path))
     } else {
       match input_path.extension() {
-        Some(ext) => {
-          if ext.to_str().unwrap() == "html" {
-            Some(PathBuf::from("index.html"))
-          } else {
-            Some(input_path.file_name().unwrap().into())
-          }
-        }
-        None => {
-          Some(input_path.file_name().unwrap().into())
+        Some(ext) if ext.to_str().unwrap() == "html" => {
+          Some(PathBuf::from("index.html"))
         }
+        _ => Some(input_path.file_name().unwrap().into()),
       }
     }
   }
 
+  pub fn is_error_page(
+    input_path: &Path,
+    error_pages: &[PathBuf],
+  ) -> bool {
+    error_pages.iter().any(|page| input_path == page)
+  }
+
+  pub fn is_netlify_file(input_path: &Path) -> bool {
+    NETLIFY_FILES
+      .iter()
+      .any(|file| input_path == Path::new(file))
+  }
+
+  pub fn url(&self) -> Option<String> {
+    let output_folder = self.output_folder.as_ref()?;
+    let output_name = self.output_name.as_ref()?;
+    if self.file_move_type == FileMoveType::TransformHtml
+      && output_name == Path::new("index.html")
+    {
+      if output_folder.as_os_str().is_empty() {
+        Some("/".to_string())
+      } else {
+        Some(format!("/{}/", output_folder.display()))
+      }
+    } else {
+      Some(format!(
+        "/{}",
+        output_folder.join(output_name).display()
+      ))
+    }
+  }
+
   pub fn sort_key(&self) -> (String, String) {
     (
       self.folder.display().to_string(),
@@ -181,8 +255,10 @@ mod test {
       output_name: Some(PathBuf::from(output_name)),
       file_move_type,
     };
-    let right =
-      FileDetails::new(&PathBuf::from(input_path));
+    let right = FileDetails::new(
+      &PathBuf::from(input_path),
+      &error_pages(&Config::default()),
+    );
     assert_eq!(left, right);
   }
 
@@ -255,6 +331,11 @@ mod test {
   #[case("subdir/about.html", "index.html")]
   #[case(".subdir/about.html", "index.html")]
   #[case("subdir/.about.html", "index.html")]
+  #[case("404.html", "404.html")]
+  #[case("500.html", "500.html")]
+  #[case("_redirects", "_redirects")]
+  #[case("_headers", "_headers")]
+  #[case("subdir/404.html", "index.html")]
   fn get_output_name_to_move(
     #[case] input_path: &str,
     #[case] output_name: &str,
@@ -262,6 +343,7 @@ mod test {
     let expected = Some(PathBuf::from(&output_name));
     let got = FileDetails::get_output_name(
       &PathBuf::from(input_path),
+      &error_pages(&Config::default()),
     );
     assert_eq!(expected, got);
   }
@@ -280,6 +362,7 @@ mod test {
   ) {
     let got = FileDetails::get_output_name(
       &PathBuf::from(input_path),
+      &error_pages(&Config::default()),
     );
     assert_eq!(expected, got);
   }
@@ -289,6 +372,9 @@ mod test {
   #[case("sub-dir/index.html", "sub-dir")]
   #[case("about.html", "about")]
   #[case("valid-dir/about.html", "valid-dir/about")]
+  #[case("404.html", "")]
+  #[case("500.html", "")]
+  #[case("sub-dir/404.html", "sub-dir/404")]
   fn get_output_dir_valid_test_html(
     #[case] input_path: &str,
     #[case] target: &str,
@@ -296,6 +382,7 @@ mod test {
     let expected = Some(PathBuf::from(target));
     let got = FileDetails::get_output_dir(
       &PathBuf::from(input_path),
+      &error_pages(&Config::default()),
     );
     assert_eq!(expected, got);
   }
@@ -313,6 +400,7 @@ mod test {
     let expected = Some(PathBuf::from(target));
     let got = FileDetails::get_output_dir(
       &PathBuf::from(input_path),
+      &error_pages(&Config::default()),
     );
     assert_eq!(expected, got);
   }
@@ -334,6 +422,7 @@ mod test {
   ) {
     let got = FileDetails::get_output_dir(
       &PathBuf::from(input_path),
+      &error_pages(&Config::default()),
     );
     assert_eq!(expected, got);
   }
@@ -356,6 +445,9 @@ mod test {
     FileMoveType::TransformHtml
   )]
   #[case("about.html", FileMoveType::TransformHtml)]
+  #[case("_redirects", FileMoveType::Copy)]
+  #[case("_headers", FileMoveType::Copy)]
+  #[case("subdir/_redirects", FileMoveType::Skip)]
   #[case(
     "subdir/about.html",
     FileMoveType::TransformHtml
@@ -374,5 +466,54 @@ mod test {
     assert_eq!(expected, got)
   }
 
+  #[rstest]
+  #[case("index.html", Some("/"))]
+  #[case("about.html", Some("/about/"))]
+  #[case("sub-dir/index.html", Some("/sub-dir/"))]
+  #[case("sub-dir/about.html", Some("/sub-dir/about/"))]
+  #[case("styles/main.css", Some("/styles/main.css"))]
+  #[case("data.json", Some("/data.json"))]
+  #[case("_includes/wrapper.html", None)]
+  #[case("valid-dir/_skip.json", None)]
+  #[case("404.html", Some("/404.html"))]
+  #[case("500.html", Some("/500.html"))]
+  #[case("sub-dir/404.html", Some("/sub-dir/404/"))]
+  fn url_test(
+    #[case] input_path: &str,
+    #[case] expected: Option<&str>,
+  ) {
+    let got = FileDetails::new(
+      &PathBuf::from(input_path),
+      &error_pages(&Config::default()),
+    )
+    .url();
+    assert_eq!(expected.map(|u| u.to_string()), got);
+  }
+
+  #[rstest]
+  #[case(
+    "errors/not-found.html",
+    Some("/errors/not-found.html")
+  )]
+  #[case("404.html", Some("/404/"))]
+  #[case("500.html", Some("/500.html"))]
+  fn url_configured_not_found_page_test(
+    #[case] input_path: &str,
+    #[case] expected: Option<&str>,
+  ) {
+    let config = Config {
+      not_found_page: PathBuf::from(
+        "errors/not-found.html",
+      ),
+      ..Config::default()
+    };
+    let got = FileDetails::new(
+      &PathBuf::from(input_path),
+      &error_pages(&config),
+    )
+    .url();
+    assert_eq!(expected.map(|u| u.to_string()), got);
+  }
+
   //
 }
@@ -3,10 +3,13 @@ use std::path::PathBuf;
 use walkdir::WalkDir;
 
 pub fn file_list(
-  content_dir: &PathBuf
+  content_dir: &PathBuf,
+  ignore: &IgnoreRules,
+  error_pages: &[PathBuf],
 ) -> Vec<FileDetails> {
   let mut file_list = WalkDir::new(content_dir)
     .into_iter()
+    .filter_entry(|e| !ignore.is_ignored(e.path()))
     .filter_map(|e| e.ok())
     .filter(|e| e.path().is_file())
     .map(|e| e.path().to_path_buf())
@@ -18,11 +21,7 @@ pub fn file_list(
       .unwrap()
       .to_path_buf()
     })
-    .filter(|pb| {
-      pb.file_name().unwrap().display().to_string()
-        != *".DS_Store".to_string()
-    })
-    .map(|pb| FileDetails::new(&pb))
+    .map(|pb| FileDetails::new(&pb, error_pages))
     .collect::<Vec<FileDetails>>();
   file_list.sort_by_key(|f| f.sort_key());
   file_list
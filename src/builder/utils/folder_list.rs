@@ -3,10 +3,12 @@ use std::path::PathBuf;
 use walkdir::WalkDir;
 
 pub fn folder_list(
-  content_dir: &PathBuf
+  content_dir: &PathBuf,
+  ignore: &IgnoreRules,
 ) -> Vec<FolderDetails> {
   let mut folders = WalkDir::new(content_dir)
     .into_iter()
+    .filter_entry(|e| !ignore.is_ignored(e.path()))
     .filter_map(|e| e.ok())
     .filter(|e| e.path().is_dir())
     .map(|e| e.path().to_path_buf())
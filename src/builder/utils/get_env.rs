@@ -1,13 +1,29 @@
-use super::highlight_code;
+use super::{
+  FileDetails, IgnoreRules, error_pages, file_list,
+  highlight_code, with_base_path,
+};
+use crate::config::Config;
+use chrono::{
+  DateTime, Local, NaiveDate, NaiveDateTime, TimeZone,
+};
+use chrono_tz::Tz;
+use globset::GlobBuilder;
 use markdown::{CompileOptions, Options};
-use minijinja::Environment;
 use minijinja::path_loader;
 use minijinja::syntax::SyntaxConfig;
-use std::path::Path;
+use minijinja::{Environment, Error, ErrorKind, Value};
+use regex::Regex;
+use std::fmt::{Display, Write};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
 
+/// The template environment. `ignore` is what
+/// `glob()` leaves out.
 pub fn get_env(
-  content_dir: &Path
+  config: &Config,
+  ignore: &IgnoreRules,
 ) -> Environment<'static> {
+  let content_dir = config.content_root.as_path();
   let mut env = Environment::new();
   env.set_syntax(
     SyntaxConfig::builder()
@@ -19,9 +35,23 @@ pub fn get_env(
   );
   env.set_lstrip_blocks(true);
   env.set_trim_blocks(true);
-  env.set_loader(path_loader(
-    content_dir.display().to_string(),
-  ));
+  // Templates in the content folder win over
+  // shared ones with the same name.
+  let content_loader =
+    path_loader(content_dir.display().to_string());
+  match &config.templates_root {
+    Some(templates_root) => {
+      let templates_loader =
+        path_loader(templates_root.display().to_string());
+      env.set_loader(move |name| {
+        match content_loader(name)? {
+          Some(source) => Ok(Some(source)),
+          None => templates_loader(name),
+        }
+      });
+    }
+    None => env.set_loader(content_loader),
+  }
   env.add_filter("highlight_css", highlight_css);
   env.add_filter("highlight_html", highlight_html);
   env.add_filter(
@@ -33,7 +63,448 @@ pub fn get_env(
   env.add_filter("highlight_python", highlight_python);
   env.add_filter("highlight_rust", highlight_rust);
   env.add_filter("markdown", mj_markdown);
+  let timezone = config.timezone.clone();
+  env.add_filter(
+    "date",
+    move |value: Value,
+          format: Option<String>,
+          zone: Option<String>| {
+      mj_date(
+        &value,
+        format.as_deref(),
+        zone.as_deref().or(timezone.as_deref()),
+      )
+    },
+  );
+  env.add_filter("slugify", mj_slugify);
+  env.add_filter("word_count", mj_word_count);
+  env.add_filter("reading_time", mj_reading_time);
+  env.add_filter("truncate_html", mj_truncate_html);
+  env.add_filter("striptags", mj_striptags);
+  env.add_filter("xml_escape", mj_xml_escape);
+  let site_url = config.site_url.clone();
+  env.add_filter("absolute_url", move |path: String| {
+    mj_absolute_url(site_url.as_deref(), &path)
+  });
+  env.add_filter("json_pretty", mj_json_pretty);
+  let build_time = chrono::Local::now();
+  let root = content_dir.to_path_buf();
+  let pages = error_pages(config);
+  let base_path = config.base_prefix();
+  env.add_function("url_for", move |path: String| {
+    mj_url_for(&root, &pages, &path)
+      .map(|url| with_base_path(&base_path, &url))
+  });
+  let root = content_dir.to_path_buf();
+  let pages = error_pages(config);
+  let base_path = config.base_prefix();
+  env.add_function("asset", move |path: String| {
+    mj_asset(&root, &pages, &path)
+      .map(|url| with_base_path(&base_path, &url))
+  });
+  let root = content_dir.to_path_buf();
+  env.add_function("read_file", move |path: String| {
+    mj_read_file(&root, &path)
+  });
+  let root = content_dir.to_path_buf();
+  let ignore = ignore.clone();
+  let pages = error_pages(config);
+  env.add_function("glob", move |pattern: String| {
+    mj_glob(&root, &ignore, &pages, &pattern)
+  });
   env
+    .add_function("now", move || build_time.to_rfc3339());
+  env
+}
+
+pub fn mj_date(
+  value: &Value,
+  format: Option<&str>,
+  timezone: Option<&str>,
+) -> Result<String, Error> {
+  let format = format.unwrap_or("%B %-d, %Y");
+  match timezone {
+    Some(name) => {
+      let zone: Tz = name.parse().map_err(|_| {
+        Error::new(
+          ErrorKind::InvalidOperation,
+          format!("date: unknown timezone '{}'", name),
+        )
+      })?;
+      format_date_in_zone(value, format, &zone)
+    }
+    None => format_date_in_zone(value, format, &Local),
+  }
+}
+
+// Timestamps and RFC 3339 strings are converted
+// into the zone. Dates and times without an offset
+// are treated as already being in it.
+fn format_date_in_zone<Z: TimeZone>(
+  value: &Value,
+  format: &str,
+  zone: &Z,
+) -> Result<String, Error>
+where
+  Z::Offset: Display,
+{
+  let datetime = if let Some(timestamp) = value.as_i64() {
+    DateTime::from_timestamp(timestamp, 0)
+      .map(|dt| dt.with_timezone(zone))
+  } else if let Some(text) = value.as_str() {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
+      Some(dt.with_timezone(zone))
+    } else if let Ok(naive) =
+      NaiveDateTime::parse_from_str(
+        text,
+        "%Y-%m-%d %H:%M:%S",
+      )
+      .or_else(|_| {
+        NaiveDateTime::parse_from_str(
+          text,
+          "%Y-%m-%dT%H:%M:%S",
+        )
+      })
+    {
+      zone.from_local_datetime(&naive).earliest()
+    } else if let Ok(date) =
+      NaiveDate::parse_from_str(text, "%Y-%m-%d")
+    {
+      zone
+        .from_local_datetime(
+          &date.and_hms_opt(0, 0, 0).unwrap(),
+        )
+        .earliest()
+    } else {
+      None
+    }
+  } else {
+    None
+  };
+  let datetime = datetime.ok_or_else(|| {
+    Error::new(
+      ErrorKind::InvalidOperation,
+      format!(
+        "date: could not parse '{}' as a date",
+        value
+      ),
+    )
+  })?;
+  let mut output = String::new();
+  write!(output, "{}", datetime.format(format)).map_err(
+    |_| {
+      Error::new(
+        ErrorKind::InvalidOperation,
+        format!(
+          "date: invalid format string '{}'",
+          format
+        ),
+      )
+    },
+  )?;
+  Ok(output)
+}
+
+pub fn mj_slugify(value: String) -> String {
+  value
+    .to_lowercase()
+    .replace(['\'', '’'], "")
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<&str>>()
+    .join("-")
+}
+
+pub fn mj_striptags(value: String) -> String {
+  let re = Regex::new(r"(?s)<!--.*?-->|<[^>]*>").unwrap();
+  re.replace_all(&value, "")
+    .split_whitespace()
+    .collect::<Vec<&str>>()
+    .join(" ")
+    .replace("&nbsp;", " ")
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&#39;", "'")
+    .replace("&amp;", "&")
+}
+
+pub fn mj_word_count(value: String) -> usize {
+  mj_striptags(value).split_whitespace().count()
+}
+
+pub fn mj_reading_time(
+  value: String,
+  words_per_minute: Option<usize>,
+) -> usize {
+  let words = mj_word_count(value);
+  words
+    .div_ceil(words_per_minute.unwrap_or(200).max(1))
+    .max(1)
+}
+
+const VOID_ELEMENTS: &[&str] = &[
+  "area", "base", "br", "col", "embed", "hr", "img",
+  "input", "link", "meta", "source", "track", "wbr",
+];
+
+/// Cuts HTML down to `length` characters of text
+/// without counting tags, then closes any tags
+/// that were left open.
+pub fn mj_truncate_html(
+  value: String,
+  length: Option<usize>,
+  end: Option<String>,
+) -> Value {
+  let length = length.unwrap_or(255);
+  let entity = Regex::new(r"^&#?[a-zA-Z0-9]+;").unwrap();
+  let mut output = String::new();
+  let mut open_tags: Vec<String> = vec![];
+  let mut count = 0;
+  let mut rest = value.as_str();
+  while !rest.is_empty() {
+    if rest.starts_with('<') {
+      let tag_end =
+        rest.find('>').map_or(rest.len(), |i| i + 1);
+      let tag = &rest[..tag_end];
+      output.push_str(tag);
+      let name: String = tag
+        .trim_start_matches("</")
+        .trim_start_matches('<')
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+      if tag.starts_with("</") {
+        if let Some(index) =
+          open_tags.iter().rposition(|open| *open == name)
+        {
+          open_tags.truncate(index);
+        }
+      } else if !tag.starts_with("<!")
+        && !tag.ends_with("/>")
+        && !name.is_empty()
+        && !VOID_ELEMENTS.contains(&name.as_str())
+      {
+        open_tags.push(name);
+      }
+      rest = &rest[tag_end..];
+    } else {
+      if count == length {
+        break;
+      }
+      let char_len = match entity.find(rest) {
+        Some(found) => found.end(),
+        None => rest.chars().next().unwrap().len_utf8(),
+      };
+      output.push_str(&rest[..char_len]);
+      count += 1;
+      rest = &rest[char_len..];
+    }
+  }
+  if mj_striptags(rest.to_string()).is_empty() {
+    return Value::from_safe_string(value);
+  }
+  output = output.trim_end().to_string();
+  output.push_str(&end.unwrap_or("...".to_string()));
+  for tag in open_tags.iter().rev() {
+    output.push_str(&format!("</{}>", tag));
+  }
+  Value::from_safe_string(output)
+}
+
+pub fn mj_xml_escape(value: String) -> String {
+  value
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+    .replace('\'', "&apos;")
+}
+
+pub fn mj_absolute_url(
+  site_url: Option<&str>,
+  path: &str,
+) -> Result<String, Error> {
+  if path.starts_with("http://")
+    || path.starts_with("https://")
+  {
+    return Ok(path.to_string());
+  }
+  match site_url {
+    Some(base) => Ok(format!(
+      "{}/{}",
+      base.trim_end_matches('/'),
+      path.trim_start_matches('/')
+    )),
+    None => Err(Error::new(
+      ErrorKind::InvalidOperation,
+      "absolute_url: `site_url` is not set in ssb.toml",
+    )),
+  }
+}
+
+pub fn mj_json_pretty(
+  value: Value
+) -> Result<String, Error> {
+  serde_json::to_string_pretty(&value).map_err(|e| {
+    Error::new(
+      ErrorKind::InvalidOperation,
+      format!("json_pretty: {}", e),
+    )
+  })
+}
+
+fn content_file(
+  content_dir: &Path,
+  path: &str,
+  function: &str,
+) -> Result<PathBuf, Error> {
+  let relative_path =
+    PathBuf::from(path.trim_start_matches('/'));
+  // Keeps paths like `../../etc/passwd` from
+  // reaching outside the content folder.
+  if relative_path.components().any(|component| {
+    !matches!(component, Component::Normal(_))
+  }) {
+    return Err(Error::new(
+      ErrorKind::InvalidOperation,
+      format!(
+        "{}: '{}' has to be a path inside the content folder",
+        function, path
+      ),
+    ));
+  }
+  if content_dir.join(&relative_path).is_file() {
+    Ok(relative_path)
+  } else {
+    Err(Error::new(
+      ErrorKind::InvalidOperation,
+      format!(
+        "{}: no file at '{}'",
+        function,
+        content_dir.join(&relative_path).display()
+      ),
+    ))
+  }
+}
+
+fn published_url(
+  relative_path: &Path,
+  error_pages: &[PathBuf],
+  function: &str,
+) -> Result<String, Error> {
+  let details =
+    FileDetails::new(relative_path, error_pages);
+  details.url().ok_or_else(|| {
+    Error::new(
+      ErrorKind::InvalidOperation,
+      format!(
+        "{}: '{}' is not published (it's inside an '_' path)",
+        function,
+        relative_path.display()
+      ),
+    )
+  })
+}
+
+pub fn mj_url_for(
+  content_dir: &Path,
+  error_pages: &[PathBuf],
+  path: &str,
+) -> Result<String, Error> {
+  let relative_path =
+    content_file(content_dir, path, "url_for")?;
+  published_url(&relative_path, error_pages, "url_for")
+}
+
+pub fn mj_asset(
+  content_dir: &Path,
+  error_pages: &[PathBuf],
+  path: &str,
+) -> Result<String, Error> {
+  let relative_path =
+    content_file(content_dir, path, "asset")?;
+  let url =
+    published_url(&relative_path, error_pages, "asset")?;
+  let bytes = fs::read(content_dir.join(&relative_path))
+    .map_err(|e| {
+      Error::new(
+        ErrorKind::InvalidOperation,
+        format!(
+          "asset: could not read '{}': {}",
+          path, e
+        ),
+      )
+    })?;
+  Ok(format!("{}?v={:08x}", url, fnv1a(&bytes)))
+}
+
+// A 32-bit FNV-1a hash. Unlike `DefaultHasher` it
+// gives the same result across builds and Rust
+// versions so asset URLs only change with their
+// content.
+fn fnv1a(bytes: &[u8]) -> u32 {
+  bytes.iter().fold(0x811c9dc5, |hash, byte| {
+    (hash ^ *byte as u32).wrapping_mul(0x01000193)
+  })
+}
+
+pub fn mj_read_file(
+  content_dir: &Path,
+  path: &str,
+) -> Result<String, Error> {
+  let relative_path =
+    content_file(content_dir, path, "read_file")?;
+  fs::read_to_string(content_dir.join(&relative_path))
+    .map_err(|e| {
+      Error::new(
+        ErrorKind::InvalidOperation,
+        format!(
+          "read_file: could not read '{}': {}",
+          path, e
+        ),
+      )
+    })
+}
+
+pub fn mj_glob(
+  content_dir: &Path,
+  ignore: &IgnoreRules,
+  error_pages: &[PathBuf],
+  pattern: &str,
+) -> Result<Value, Error> {
+  let matcher = GlobBuilder::new(pattern)
+    .literal_separator(true)
+    .build()
+    .map_err(|e| {
+      Error::new(
+        ErrorKind::InvalidOperation,
+        format!(
+          "glob: invalid pattern '{}': {}",
+          pattern, e
+        ),
+      )
+    })?
+    .compile_matcher();
+  let matches: Vec<FileDetails> = file_list(
+    &content_dir.to_path_buf(),
+    ignore,
+    error_pages,
+  )
+  .into_iter()
+  .filter(|details| {
+    matcher.is_match(details.folder.join(&details.name))
+  })
+  .collect();
+  if matches.is_empty() {
+    Err(Error::new(
+      ErrorKind::InvalidOperation,
+      format!("glob: no files match '{}'", pattern),
+    ))
+  } else {
+    Ok(Value::from_serialize(matches))
+  }
 }
 
 pub fn highlight_css(code: String) -> String {
@@ -79,3 +550,341 @@ pub fn mj_markdown(value: String) -> String {
     Err(_e) => "[unable to parse markdown]".to_string(),
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case(
+    "2025-09-06",
+    "%B %-d, %Y",
+    "UTC",
+    "September 6, 2025"
+  )]
+  #[case(
+    "2025-09-06 14:30:00",
+    "%Y-%m-%d %H:%M",
+    "America/New_York",
+    "2025-09-06 14:30"
+  )]
+  #[case(
+    "2025-09-06T02:00:00Z",
+    "%Y-%m-%d %H:%M",
+    "America/New_York",
+    "2025-09-05 22:00"
+  )]
+  fn date_test(
+    #[case] input: &str,
+    #[case] format: &str,
+    #[case] timezone: &str,
+    #[case] expected: &str,
+  ) {
+    let got = mj_date(
+      &Value::from(input),
+      Some(format),
+      Some(timezone),
+    )
+    .unwrap();
+    assert_eq!(expected, got);
+  }
+
+  #[test]
+  fn date_from_timestamp_test() {
+    let got = mj_date(
+      &Value::from(0),
+      Some("%Y-%m-%d"),
+      Some("UTC"),
+    )
+    .unwrap();
+    assert_eq!("1970-01-01", got);
+  }
+
+  #[rstest]
+  #[case("not a date", None, Some("UTC"))]
+  #[case("2025-09-06", None, Some("Not/A_Zone"))]
+  fn date_error_test(
+    #[case] input: &str,
+    #[case] format: Option<&str>,
+    #[case] timezone: Option<&str>,
+  ) {
+    assert!(
+      mj_date(&Value::from(input), format, timezone)
+        .is_err()
+    );
+  }
+
+  #[rstest]
+  #[case("Hello World", "hello-world")]
+  #[case("  Alan's   Site!  ", "alans-site")]
+  #[case("v0.3.0 Release Notes", "v0-3-0-release-notes")]
+  #[case("Crème Brûlée", "crème-brûlée")]
+  fn slugify_test(
+    #[case] input: &str,
+    #[case] expected: &str,
+  ) {
+    assert_eq!(expected, mj_slugify(input.to_string()));
+  }
+
+  #[rstest]
+  #[case(
+    "<p>one <em>two</em></p>\n<p>three</p>",
+    "one two three"
+  )]
+  #[case("a <!-- note --> b", "a b")]
+  #[case("<p>fish &amp; chips</p>", "fish & chips")]
+  fn striptags_test(
+    #[case] input: &str,
+    #[case] expected: &str,
+  ) {
+    assert_eq!(expected, mj_striptags(input.to_string()));
+  }
+
+  #[rstest]
+  #[case("", 0)]
+  #[case("the quick brown fox", 4)]
+  #[case("<p>the <strong>quick</strong> brown</p>", 3)]
+  fn word_count_test(
+    #[case] input: &str,
+    #[case] expected: usize,
+  ) {
+    assert_eq!(
+      expected,
+      mj_word_count(input.to_string())
+    );
+  }
+
+  #[rstest]
+  #[case(10, None, 1)]
+  #[case(200, None, 1)]
+  #[case(201, None, 2)]
+  #[case(300, Some(100), 3)]
+  fn reading_time_test(
+    #[case] words: usize,
+    #[case] words_per_minute: Option<usize>,
+    #[case] expected: usize,
+  ) {
+    let input = vec!["word"; words].join(" ");
+    assert_eq!(
+      expected,
+      mj_reading_time(input, words_per_minute)
+    );
+  }
+
+  #[rstest]
+  #[case("<p>short</p>", 10, "<p>short</p>")]
+  #[case(
+    "<p>the quick brown</p>",
+    9,
+    "<p>the quick...</p>"
+  )]
+  #[case(
+    "<p>the <em>quick brown</em> fox</p>",
+    7,
+    "<p>the <em>qui...</em></p>"
+  )]
+  #[case("<p>a&amp;b cdef</p>", 3, "<p>a&amp;b...</p>")]
+  #[case(
+    "<p>one<br>two three</p>",
+    6,
+    "<p>one<br>two...</p>"
+  )]
+  fn truncate_html_test(
+    #[case] input: &str,
+    #[case] length: usize,
+    #[case] expected: &str,
+  ) {
+    let got = mj_truncate_html(
+      input.to_string(),
+      Some(length),
+      None,
+    );
+    assert_eq!(expected, got.as_str().unwrap());
+  }
+
+  #[rstest]
+  #[case(
+    "<a href=\"x\">Tom & 'Jerry'</a>",
+    "&lt;a href=&quot;x&quot;&gt;Tom &amp; &apos;Jerry&apos;&lt;/a&gt;"
+  )]
+  fn xml_escape_test(
+    #[case] input: &str,
+    #[case] expected: &str,
+  ) {
+    assert_eq!(
+      expected,
+      mj_xml_escape(input.to_string())
+    );
+  }
+
+  #[rstest]
+  #[case(
+    Some("https://example.com"),
+    "/about/",
+    "https://example.com/about/"
+  )]
+  #[case(
+    Some("https://example.com/"),
+    "about/",
+    "https://example.com/about/"
+  )]
+  #[case(
+    None,
+    "https://other.example.com/",
+    "https://other.example.com/"
+  )]
+  fn absolute_url_test(
+    #[case] site_url: Option<&str>,
+    #[case] path: &str,
+    #[case] expected: &str,
+  ) {
+    assert_eq!(
+      expected,
+      mj_absolute_url(site_url, path).unwrap()
+    );
+  }
+
+  #[test]
+  fn absolute_url_without_site_url_test() {
+    assert!(mj_absolute_url(None, "/about/").is_err());
+  }
+
+  #[test]
+  fn json_pretty_test() {
+    let value = Value::from_serialize(
+      serde_json::json!({ "alfa": ["bravo"] }),
+    );
+    assert_eq!(
+      "{\n  \"alfa\": [\n    \"bravo\"\n  ]\n}",
+      mj_json_pretty(value).unwrap()
+    );
+  }
+
+  fn pages() -> Vec<PathBuf> {
+    error_pages(&Config::default())
+  }
+
+  #[rstest]
+  #[case("index.html", Some("/"))]
+  #[case("404.html", Some("/404.html"))]
+  #[case(
+    "/examples/forms/contact.html",
+    Some("/examples/forms/contact/")
+  )]
+  #[case("_release-notes/v0.3.0.md", None)]
+  #[case("missing.html", None)]
+  #[case("../Cargo.toml", None)]
+  fn url_for_test(
+    #[case] path: &str,
+    #[case] expected: Option<&str>,
+  ) {
+    assert_eq!(
+      expected.map(|url| url.to_string()),
+      mj_url_for(Path::new("content"), &pages(), path)
+        .ok()
+    );
+  }
+
+  #[test]
+  fn asset_test() {
+    let path = "styles/highlight-base16-ocean-dark.css";
+    let bytes =
+      fs::read(Path::new("content").join(path)).unwrap();
+    assert_eq!(
+      format!("/{}?v={:08x}", path, fnv1a(&bytes)),
+      mj_asset(Path::new("content"), &pages(), path)
+        .unwrap()
+    );
+  }
+
+  #[rstest]
+  #[case("missing.css")]
+  #[case("_release-notes/v0.3.0.md")]
+  #[case("../Cargo.toml")]
+  fn asset_error_test(#[case] path: &str) {
+    assert!(
+      mj_asset(Path::new("content"), &pages(), path)
+        .is_err()
+    );
+  }
+
+  #[rstest]
+  #[case(b"", 0x811c9dc5)]
+  #[case(b"a", 0xe40c292c)]
+  #[case(b"foobar", 0xbf9cf968)]
+  fn fnv1a_test(
+    #[case] bytes: &[u8],
+    #[case] expected: u32,
+  ) {
+    assert_eq!(expected, fnv1a(bytes));
+  }
+
+  #[test]
+  fn read_file_test() {
+    let path = "_release-notes/v0.3.0.md";
+    assert_eq!(
+      fs::read_to_string(Path::new("content").join(path))
+        .unwrap(),
+      mj_read_file(Path::new("content"), path).unwrap()
+    );
+  }
+
+  #[rstest]
+  #[case("missing.txt")]
+  #[case("../Cargo.toml")]
+  #[case("/../../etc/passwd")]
+  #[case("./index.html")]
+  #[case("styles")]
+  fn read_file_error_test(#[case] path: &str) {
+    assert!(
+      mj_read_file(Path::new("content"), path).is_err()
+    );
+  }
+
+  #[rstest]
+  #[case("styles/*.css", true)]
+  #[case("*.css", false)]
+  #[case("missing/*", false)]
+  #[case("[", false)]
+  fn glob_test(
+    #[case] pattern: &str,
+    #[case] expected: bool,
+  ) {
+    let config = Config::default();
+    let ignore = IgnoreRules::new(&config);
+    assert_eq!(
+      expected,
+      mj_glob(
+        &config.content_root,
+        &ignore,
+        &error_pages(&config),
+        pattern,
+      )
+      .is_ok()
+    );
+  }
+
+  #[rstest]
+  #[case("index.html", true)]
+  #[case("wrappers/main.html", true)]
+  #[case("missing.html", false)]
+  fn templates_root_test(
+    #[case] name: &str,
+    #[case] expected: bool,
+  ) {
+    let config = Config {
+      templates_root: Some(PathBuf::from(
+        "content/_includes",
+      )),
+      ..Config::default()
+    };
+    assert_eq!(
+      expected,
+      get_env(&config, &IgnoreRules::new(&config))
+        .get_template(name)
+        .is_ok()
+    );
+  }
+}
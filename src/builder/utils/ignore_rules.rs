@@ -0,0 +1,181 @@
+use crate::config::Config;
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+use tracing::error;
+
+/// The ignore file read from the project folder
+/// and the folders that are watched
+pub const IGNORE_FILE: &str = ".ssbignore";
+
+/// Editor backups, swap files and the like that
+/// are never part of the site. A `!` line in an
+/// ignore file can bring any of them back.
+pub const DEFAULT_IGNORES: &[&str] = &[
+  ".DS_Store",
+  ".ssbignore",
+  ".git/",
+  "*~",
+  "*.swp",
+  "*.swo",
+  "*.swx",
+  ".#*",
+  "\\#*#",
+];
+
+/// What the watcher and the builder skip. Rules
+/// come from `.ssbignore` files (and `.gitignore`
+/// files when `use_gitignore` is on) with gitignore
+/// semantics: patterns are relative to the folder
+/// their file is in and files in deeper folders
+/// take precedence. Ignore files are read from
+/// each watched folder and the folders above it
+/// up to the project folder.
+#[derive(Clone, Debug, Default)]
+pub struct IgnoreRules {
+  roots: Vec<PathBuf>,
+  use_gitignore: bool,
+  matchers: Vec<Gitignore>,
+}
+
+impl IgnoreRules {
+  pub fn new(config: &Config) -> IgnoreRules {
+    let roots = config
+      .watch_roots()
+      .iter()
+      .filter_map(|root| std::path::absolute(root).ok())
+      .collect();
+    IgnoreRules::load(roots, config.use_gitignore)
+  }
+
+  /// Reads the ignore files again (e.g. after one
+  /// of them changed).
+  pub fn reload(&self) -> IgnoreRules {
+    IgnoreRules::load(
+      self.roots.clone(),
+      self.use_gitignore,
+    )
+  }
+
+  fn load(
+    roots: Vec<PathBuf>,
+    use_gitignore: bool,
+  ) -> IgnoreRules {
+    let names = if use_gitignore {
+      vec![".gitignore", IGNORE_FILE]
+    } else {
+      vec![IGNORE_FILE]
+    };
+    let project = std::path::absolute(".")
+      .unwrap_or_else(|_| PathBuf::from("/"));
+    let mut dirs = roots
+      .iter()
+      .flat_map(|root| {
+        root
+          .ancestors()
+          .enumerate()
+          .take_while(|(i, dir)| {
+            *i == 0 || dir.starts_with(&project)
+          })
+          .map(|(_, dir)| dir.to_path_buf())
+          .collect::<Vec<_>>()
+      })
+      .collect::<Vec<_>>();
+    // Shallower folders first so deeper ones win.
+    dirs.sort_by_key(|dir| dir.components().count());
+    dirs.dedup();
+    let mut matchers = vec![default_matcher()];
+    dirs.iter().for_each(|dir| {
+      names.iter().for_each(|name| {
+        let file = dir.join(name);
+        if file.is_file() {
+          let (matcher, e) = Gitignore::new(&file);
+          if let Some(e) = e {
+            error!("{}: {}", file.display(), e);
+          }
+          matchers.push(matcher);
+        }
+      })
+    });
+    IgnoreRules {
+      roots,
+      use_gitignore,
+      matchers,
+    }
+  }
+
+  /// True if the path or a folder it's in is
+  /// ignored.
+  pub fn is_ignored(
+    &self,
+    path: &Path,
+  ) -> bool {
+    let Ok(path) = std::path::absolute(path) else {
+      return false;
+    };
+    let is_dir = path.is_dir();
+    self
+      .matchers
+      .iter()
+      .filter(|matcher| path.starts_with(matcher.path()))
+      .fold(false, |ignored, matcher| {
+        match matcher
+          .matched_path_or_any_parents(&path, is_dir)
+        {
+          Match::Ignore(_) => true,
+          Match::Whitelist(_) => false,
+          Match::None => ignored,
+        }
+      })
+  }
+
+  /// True for files whose changes mean the rules
+  /// need to be read again.
+  pub fn is_ignore_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| {
+      name == IGNORE_FILE || name == ".gitignore"
+    })
+  }
+}
+
+fn default_matcher() -> Gitignore {
+  let mut builder = GitignoreBuilder::new("/");
+  DEFAULT_IGNORES.iter().for_each(|pattern| {
+    let _ = builder.add_line(None, pattern);
+  });
+  builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("index.html", false)]
+  #[case(".well-known/security.txt", false)]
+  #[case(".DS_Store", true)]
+  #[case("about/index.html~", true)]
+  #[case("about/.index.html.swp", true)]
+  #[case("node_modules/a/index.js", true)]
+  #[case("scratch/notes.html", true)]
+  #[case("scratch/keep.html", false)]
+  #[case("drafts/a.html", true)]
+  #[case("about/drafts/a.html", false)]
+  #[case("about/debug.log", true)]
+  fn is_ignored_test(
+    #[case] path: &str,
+    #[case] expected: bool,
+  ) {
+    let content =
+      std::path::absolute("tests/fixtures/ignore")
+        .unwrap();
+    let rules =
+      IgnoreRules::load(vec![content.clone()], false);
+    assert_eq!(
+      expected,
+      rules.is_ignored(&content.join(path))
+    );
+  }
+}
@@ -0,0 +1,153 @@
+use minijinja::Value;
+use minijinja::value::{Enumerator, Object};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Debug, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+pub type FileLoader =
+  Box<dyn Fn(&Path) -> Option<Value> + Send + Sync>;
+
+/// A template map keyed by content path whose
+/// values are only loaded when a template asks
+/// for them. Each entry is loaded at most once
+/// per build.
+pub struct LazyFiles {
+  content_root: PathBuf,
+  keys: BTreeSet<String>,
+  loader: FileLoader,
+  cache: Mutex<BTreeMap<String, Option<Value>>>,
+}
+
+impl LazyFiles {
+  pub fn new(
+    content_root: &Path,
+    keys: BTreeSet<String>,
+    loader: impl Fn(&Path) -> Option<Value>
+    + Send
+    + Sync
+    + 'static,
+  ) -> LazyFiles {
+    LazyFiles {
+      content_root: content_root.to_path_buf(),
+      keys,
+      loader: Box::new(loader),
+      cache: Mutex::new(BTreeMap::new()),
+    }
+  }
+}
+
+impl LazyFiles {
+  /// Entries that were already loaded (e.g. to
+  /// check them during the build) so the loader
+  /// isn't called for them.
+  pub fn with_loaded(
+    self,
+    loaded: BTreeMap<String, Option<Value>>,
+  ) -> LazyFiles {
+    LazyFiles {
+      cache: Mutex::new(loaded),
+      ..self
+    }
+  }
+
+  /// The content paths that can be loaded.
+  pub fn keys(&self) -> &BTreeSet<String> {
+    &self.keys
+  }
+}
+
+impl Debug for LazyFiles {
+  fn fmt(
+    &self,
+    f: &mut Formatter<'_>,
+  ) -> std::fmt::Result {
+    f.debug_struct("LazyFiles")
+      .field("content_root", &self.content_root)
+      .field("keys", &self.keys)
+      .finish()
+  }
+}
+
+impl Object for LazyFiles {
+  fn get_value(
+    self: &Arc<Self>,
+    key: &Value,
+  ) -> Option<Value> {
+    let key = key.as_str()?;
+    if !self.keys.contains(key) {
+      return None;
+    }
+    let mut cache = self.cache.lock().unwrap();
+    cache
+      .entry(key.to_string())
+      .or_insert_with(|| {
+        (self.loader)(&self.content_root.join(key))
+      })
+      .clone()
+  }
+
+  fn enumerate(self: &Arc<Self>) -> Enumerator {
+    Enumerator::Values(
+      self.keys.iter().map(Value::from).collect(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  #[test]
+  fn lazy_files_test() {
+    let loads = Arc::new(AtomicUsize::new(0));
+    let counter = loads.clone();
+    let files = Value::from_object(LazyFiles::new(
+      Path::new("data"),
+      BTreeSet::from([
+        "alfa.json".to_string(),
+        "bravo.json".to_string(),
+      ]),
+      move |path| {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Some(Value::from(path.display().to_string()))
+      },
+    ));
+    let keys = files
+      .try_iter()
+      .unwrap()
+      .map(|key| key.to_string())
+      .collect::<Vec<_>>();
+    assert_eq!(vec!["alfa.json", "bravo.json"], keys);
+    assert_eq!(0, loads.load(Ordering::Relaxed));
+    let alfa = files.get_item(&Value::from("alfa.json"));
+    assert_eq!(
+      "data/alfa.json",
+      alfa.unwrap().to_string()
+    );
+    let _ = files.get_item(&Value::from("alfa.json"));
+    let _ = files.get_item(&Value::from("charlie.json"));
+    assert_eq!(1, loads.load(Ordering::Relaxed));
+  }
+
+  #[test]
+  fn with_loaded_test() {
+    let files = Value::from_object(
+      LazyFiles::new(
+        Path::new("data"),
+        BTreeSet::from(["alfa.json".to_string()]),
+        |_| panic!("already loaded"),
+      )
+      .with_loaded(BTreeMap::from([(
+        "alfa.json".to_string(),
+        Some(Value::from(1)),
+      )])),
+    );
+    assert_eq!(
+      Some(Value::from(1)),
+      files.get_item(&Value::from("alfa.json")).ok()
+    );
+  }
+}
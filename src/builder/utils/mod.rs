@@ -1,3 +1,4 @@
+pub mod base_path;
 pub mod copy_file_with_mkdir;
 pub mod empty_dir;
 pub mod file_details;
@@ -6,9 +7,16 @@ pub mod folder_details;
 pub mod folder_list;
 pub mod get_env;
 pub mod highlight_code;
+pub mod ignore_rules;
+pub mod lazy_files;
+pub mod output_hashes;
+pub mod parse_data;
+pub mod precompress_file;
 pub mod trim_empty_leading_lines;
+pub mod validate_data;
 pub mod write_file_with_mkdir;
 
+pub use self::base_path::*;
 pub use self::copy_file_with_mkdir::*;
 pub use self::empty_dir::*;
 pub use self::file_details::*;
@@ -17,5 +25,11 @@ pub use self::folder_details::*;
 pub use self::folder_list::*;
 pub use self::get_env::*;
 pub use self::highlight_code::*;
+pub use self::ignore_rules::*;
+pub use self::lazy_files::*;
+pub use self::output_hashes::*;
+pub use self::parse_data::*;
+pub use self::precompress_file::*;
 pub use self::trim_empty_leading_lines::*;
+pub use self::validate_data::*;
 pub use self::write_file_with_mkdir::*;
@@ -0,0 +1,140 @@
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// A hash of each file in the output folder keyed
+/// by the URL path it's served at.
+pub type OutputHashes = BTreeMap<String, u64>;
+
+/// Hashes the files in the output folder. The
+/// precompressed `.gz` and `.br` copies are skipped
+/// since they change along with their source file.
+pub fn output_hashes(output_root: &Path) -> OutputHashes {
+  WalkDir::new(output_root)
+    .into_iter()
+    .filter_map(|entry| entry.ok())
+    .filter(|entry| {
+      entry.file_type().is_file()
+        && !entry.path().extension().is_some_and(
+          |extension| {
+            extension == "gz" || extension == "br"
+          },
+        )
+    })
+    .filter_map(|entry| {
+      let relative_path =
+        entry.path().strip_prefix(output_root).ok()?;
+      let content = std::fs::read(entry.path()).ok()?;
+      Some((
+        format!(
+          "/{}",
+          relative_path
+            .display()
+            .to_string()
+            .replace('\\', "/")
+        ),
+        output_hash(relative_path, &content),
+      ))
+    })
+    .collect()
+}
+
+/// Hashes a file's content. In pages the version
+/// strings that `asset()` adds are left out so a
+/// stylesheet change doesn't count as a change to
+/// every page that links to it.
+pub fn output_hash(
+  path: &Path,
+  content: &[u8],
+) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  if path.extension().is_some_and(|ext| ext == "html") {
+    let version = Regex::new(r"\?v=[0-9a-f]{8}").unwrap();
+    version
+      .replace_all(&String::from_utf8_lossy(content), "")
+      .hash(&mut hasher);
+  } else {
+    content.hash(&mut hasher);
+  }
+  hasher.finish()
+}
+
+/// Lists the URL paths that were added, removed or
+/// changed between two builds.
+pub fn changed_outputs(
+  before: &OutputHashes,
+  after: &OutputHashes,
+) -> Vec<String> {
+  after
+    .iter()
+    .filter(|(path, hash)| {
+      before.get(*path) != Some(hash)
+    })
+    .map(|(path, _)| path.clone())
+    .chain(
+      before
+        .keys()
+        .filter(|path| !after.contains_key(*path))
+        .cloned(),
+    )
+    .collect()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case(
+    "index.html",
+    r#"<link href="/main.css?v=0a1b2c3d">"#,
+    r#"<link href="/main.css?v=4e5f6a7b">"#,
+    true
+  )]
+  #[case(
+    "index.html",
+    r#"<p>alfa</p>"#,
+    r#"<p>bravo</p>"#,
+    false
+  )]
+  #[case(
+    "main.css",
+    "body { color: red; }",
+    "body { color: blue; }",
+    false
+  )]
+  fn output_hash_test(
+    #[case] path: &str,
+    #[case] a: &str,
+    #[case] b: &str,
+    #[case] same: bool,
+  ) {
+    assert_eq!(
+      same,
+      output_hash(Path::new(path), a.as_bytes())
+        == output_hash(Path::new(path), b.as_bytes())
+    );
+  }
+
+  #[test]
+  fn changed_outputs_test() {
+    let before = OutputHashes::from([
+      ("/index.html".to_string(), 1),
+      ("/main.css".to_string(), 2),
+      ("/old.js".to_string(), 3),
+    ]);
+    let after = OutputHashes::from([
+      ("/index.html".to_string(), 1),
+      ("/main.css".to_string(), 4),
+      ("/new.js".to_string(), 5),
+    ]);
+    assert_eq!(
+      vec!["/main.css", "/new.js", "/old.js"],
+      changed_outputs(&before, &after)
+    );
+  }
+}
@@ -0,0 +1,243 @@
+use anyhow::{Result, anyhow};
+use minijinja::Value;
+
+pub const DATA_EXTENSIONS: &[&str] = &[
+  "csv", "json", "jsonl", "ndjson", "toml", "yaml", "yml",
+];
+
+/// Parses the text of a data file into a template
+/// value based on its extension. Error messages
+/// include the line the problem is on.
+pub fn parse_data(
+  text: &str,
+  extension: &str,
+  infer_csv_types: bool,
+) -> Result<Value> {
+  match extension {
+    "csv" => parse_csv(text, infer_csv_types),
+    "json" => Ok(serde_json::from_str::<Value>(text)?),
+    "jsonl" | "ndjson" => parse_ndjson(text),
+    "toml" => Ok(toml_to_value(toml::from_str(text)?)),
+    "yaml" | "yml" => {
+      Ok(serde_yaml::from_str::<Value>(text)?)
+    }
+    _ => Err(anyhow!(
+      "Unsupported data format: {}",
+      extension
+    )),
+  }
+}
+
+fn parse_csv(
+  text: &str,
+  infer_types: bool,
+) -> Result<Value> {
+  let mut reader =
+    csv::Reader::from_reader(text.as_bytes());
+  let headers = reader.headers()?.clone();
+  let mut rows = vec![];
+  for record in reader.records() {
+    let record = record?;
+    rows.push(Value::from_iter(
+      headers.iter().zip(record.iter()).map(
+        |(header, cell)| {
+          let value = if infer_types {
+            infer_csv_type(cell)
+          } else {
+            Value::from(cell)
+          };
+          (header.to_string(), value)
+        },
+      ),
+    ));
+  }
+  Ok(Value::from(rows))
+}
+
+// Codes with leading zeros (e.g. "007") and
+// words like "nan" and "inf" stay strings.
+fn infer_csv_type(cell: &str) -> Value {
+  let trimmed = cell.trim();
+  let digits = trimmed.trim_start_matches(['-', '+']);
+  let leading_zero = digits.len() > 1
+    && digits.starts_with('0')
+    && digits[1..]
+      .starts_with(|c: char| c.is_ascii_digit());
+  if trimmed.is_empty() {
+    Value::from(())
+  } else if leading_zero {
+    Value::from(cell)
+  } else if let Ok(number) = trimmed.parse::<i64>() {
+    Value::from(number)
+  } else if let Some(number) = trimmed
+    .parse::<f64>()
+    .ok()
+    .filter(|number| number.is_finite())
+  {
+    Value::from(number)
+  } else if trimmed.eq_ignore_ascii_case("true") {
+    Value::from(true)
+  } else if trimmed.eq_ignore_ascii_case("false") {
+    Value::from(false)
+  } else {
+    Value::from(cell)
+  }
+}
+
+fn parse_ndjson(text: &str) -> Result<Value> {
+  let mut items = vec![];
+  for (index, line) in text.lines().enumerate() {
+    if line.trim().is_empty() {
+      continue;
+    }
+    match serde_json::from_str::<Value>(line) {
+      Ok(item) => items.push(item),
+      Err(e) => {
+        return Err(anyhow!("line {}: {}", index + 1, e));
+      }
+    }
+  }
+  Ok(Value::from(items))
+}
+
+// TOML datetimes don't map onto template values
+// directly so they're converted to strings.
+fn toml_to_value(toml_value: toml::Value) -> Value {
+  match toml_value {
+    toml::Value::String(text) => Value::from(text),
+    toml::Value::Integer(number) => Value::from(number),
+    toml::Value::Float(number) => Value::from(number),
+    toml::Value::Boolean(flag) => Value::from(flag),
+    toml::Value::Datetime(datetime) => {
+      Value::from(datetime.to_string())
+    }
+    toml::Value::Array(items) => Value::from(
+      items
+        .into_iter()
+        .map(toml_to_value)
+        .collect::<Vec<_>>(),
+    ),
+    toml::Value::Table(table) => Value::from_iter(
+      table
+        .into_iter()
+        .map(|(key, item)| (key, toml_to_value(item))),
+    ),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+  use serde_json::json;
+
+  #[rstest]
+  #[case("json", r#"{"alfa": [1, "bravo"]}"#)]
+  #[case("toml", "alfa = [1, \"bravo\"]")]
+  #[case("yaml", "alfa:\n  - 1\n  - bravo")]
+  #[case("yml", "alfa: [1, bravo]")]
+  fn parse_data_formats_test(
+    #[case] extension: &str,
+    #[case] text: &str,
+  ) {
+    let got = parse_data(text, extension, false).unwrap();
+    assert_eq!(
+      r#"{"alfa": [1, "bravo"]}"#,
+      got.to_string()
+    );
+  }
+
+  #[test]
+  fn parse_data_toml_datetime_test() {
+    let got =
+      parse_data("updated = 2025-09-06", "toml", false)
+        .unwrap();
+    assert_eq!(
+      r#"{"updated": "2025-09-06"}"#,
+      got.to_string()
+    );
+  }
+
+  #[rstest]
+  #[case(
+    false,
+    json!([
+      {"count": "1", "name": "alfa", "ok": "true"},
+      {"count": "", "name": "bravo", "ok": "false"},
+    ])
+  )]
+  #[case(
+    true,
+    json!([
+      {"count": 1, "name": "alfa", "ok": true},
+      {"count": null, "name": "bravo", "ok": false},
+    ])
+  )]
+  fn parse_data_csv_test(
+    #[case] infer_types: bool,
+    #[case] expected: serde_json::Value,
+  ) {
+    let text =
+      "name,count,ok\nalfa,1,true\nbravo,,false\n";
+    let got =
+      parse_data(text, "csv", infer_types).unwrap();
+    assert_eq!(
+      expected,
+      serde_json::to_value(got).unwrap()
+    );
+  }
+
+  #[rstest]
+  #[case("0", json!(0))]
+  #[case("-12", json!(-12))]
+  #[case("0.5", json!(0.5))]
+  #[case("1e3", json!(1000.0))]
+  #[case("007", json!("007"))]
+  #[case("-007", json!("-007"))]
+  #[case("00.5", json!("00.5"))]
+  #[case("nan", json!("nan"))]
+  #[case("inf", json!("inf"))]
+  #[case("-Infinity", json!("-Infinity"))]
+  #[case("1e400", json!("1e400"))]
+  #[case("TRUE", json!(true))]
+  #[case(" ", json!(null))]
+  fn infer_csv_type_test(
+    #[case] cell: &str,
+    #[case] expected: serde_json::Value,
+  ) {
+    assert_eq!(
+      expected,
+      serde_json::to_value(infer_csv_type(cell)).unwrap()
+    );
+  }
+
+  #[rstest]
+  #[case("ndjson")]
+  #[case("jsonl")]
+  fn parse_data_ndjson_test(#[case] extension: &str) {
+    let text = "{\"id\": 1}\n\n{\"id\": 2}\n";
+    let got = parse_data(text, extension, false).unwrap();
+    assert_eq!(
+      r#"[{"id": 1}, {"id": 2}]"#,
+      got.to_string()
+    );
+  }
+
+  #[rstest]
+  #[case("json", "{\n  \"alfa\": \n}", "line 3")]
+  #[case("toml", "alfa = 1\nbravo = \n", "line 2")]
+  #[case("yaml", "alfa: 1\nbravo: [\n", "line 3")]
+  #[case("csv", "alfa,bravo\n1,2\n3\n", "line: 3")]
+  #[case("ndjson", "{\"id\": 1}\n{\"id\": }\n", "line 2")]
+  fn parse_data_error_line_test(
+    #[case] extension: &str,
+    #[case] text: &str,
+    #[case] line: &str,
+  ) {
+    let error = parse_data(text, extension, false)
+      .unwrap_err()
+      .to_string();
+    assert!(error.contains(line), "{}", error);
+  }
+}
@@ -0,0 +1,79 @@
+use anyhow::Result;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::io::Write;
+use std::path::Path;
+
+pub const PRECOMPRESS_EXTENSIONS: &[&str] =
+  &["css", "html", "js", "json", "svg"];
+
+/// Writes `.gz` and `.br` copies next to a file
+/// for hosts that serve precompressed files.
+pub fn precompress_file(path: &Path) -> Result<()> {
+  let content = std::fs::read(path)?;
+  let mut gz_path = path.as_os_str().to_owned();
+  gz_path.push(".gz");
+  std::fs::write(gz_path, compress_gzip(&content)?)?;
+  let mut br_path = path.as_os_str().to_owned();
+  br_path.push(".br");
+  std::fs::write(br_path, compress_brotli(&content)?)?;
+  Ok(())
+}
+
+pub fn compress_gzip(content: &[u8]) -> Result<Vec<u8>> {
+  let mut encoder =
+    GzEncoder::new(Vec::new(), Compression::best());
+  encoder.write_all(content)?;
+  Ok(encoder.finish()?)
+}
+
+pub fn compress_brotli(
+  content: &[u8]
+) -> Result<Vec<u8>> {
+  let mut output = Vec::new();
+  {
+    let mut encoder = brotli::CompressorWriter::new(
+      &mut output,
+      4096,
+      11,
+      22,
+    );
+    encoder.write_all(content)?;
+  }
+  Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::io::Read;
+
+  const CONTENT: &str =
+    "<p>alfa bravo charlie</p><p>alfa bravo charlie</p>";
+
+  #[test]
+  fn compress_gzip_test() {
+    let compressed =
+      compress_gzip(CONTENT.as_bytes()).unwrap();
+    let mut got = String::new();
+    flate2::read::GzDecoder::new(compressed.as_slice())
+      .read_to_string(&mut got)
+      .unwrap();
+    assert_eq!(CONTENT, got);
+  }
+
+  #[test]
+  fn compress_brotli_test() {
+    let compressed =
+      compress_brotli(CONTENT.as_bytes()).unwrap();
+    let mut got = String::new();
+    brotli::Decompressor::new(
+      compressed.as_slice(),
+      4096,
+    )
+    .read_to_string(&mut got)
+    .unwrap();
+    assert_eq!(CONTENT, got);
+  }
+}
@@ -0,0 +1,92 @@
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+
+/// Checks data against a JSON Schema and returns a
+/// message for each violation that starts with the
+/// JSON pointer to the value that failed.
+pub fn validate_data(
+  schema: &Value,
+  data: &Value,
+) -> Result<Vec<String>> {
+  let validator = jsonschema::validator_for(schema)
+    .map_err(|e| anyhow!("Invalid schema: {}", e))?;
+  Ok(
+    validator
+      .iter_errors(data)
+      .map(|error| {
+        let pointer = error.instance_path.to_string();
+        if pointer.is_empty() {
+          format!("(root): {}", error)
+        } else {
+          format!("{}: {}", pointer, error)
+        }
+      })
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use serde_json::json;
+
+  fn schema() -> Value {
+    json!({
+      "type": "object",
+      "required": ["dataset"],
+      "properties": {
+        "dataset": {
+          "type": "object",
+          "properties": {
+            "alfa": { "type": "string" },
+            "bravo": {
+              "type": "array",
+              "items": { "type": "string" }
+            }
+          }
+        }
+      }
+    })
+  }
+
+  #[test]
+  fn validate_data_valid_test() {
+    let data = json!({
+      "dataset": { "alfa": "fox", "bravo": ["the", "fox"] }
+    });
+    let got = validate_data(&schema(), &data).unwrap();
+    assert_eq!(Vec::<String>::new(), got);
+  }
+
+  #[test]
+  fn validate_data_pointer_test() {
+    let data = json!({
+      "dataset": { "alfa": 1, "bravo": ["the", 2] }
+    });
+    let got = validate_data(&schema(), &data).unwrap();
+    assert_eq!(
+      vec![
+        r#"/dataset/alfa: 1 is not of type "string""#,
+        r#"/dataset/bravo/1: 2 is not of type "string""#,
+      ],
+      got
+    );
+  }
+
+  #[test]
+  fn validate_data_root_test() {
+    let got =
+      validate_data(&schema(), &json!({})).unwrap();
+    assert_eq!(
+      vec![r#"(root): "dataset" is a required property"#],
+      got
+    );
+  }
+
+  #[test]
+  fn validate_data_invalid_schema_test() {
+    let schema = json!({ "type": "not-a-type" });
+    assert!(validate_data(&schema, &json!({})).is_err());
+  }
+}
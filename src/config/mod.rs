@@ -1,11 +1,58 @@
-use std::path::PathBuf;
+use crate::server::throttle::ThrottleRule;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
 pub struct Config {
   pub content_root: PathBuf,
   pub output_root: PathBuf,
   pub logs_root: PathBuf,
   pub debug: bool,
+  pub site_url: Option<String>,
+  pub timezone: Option<String>,
+  pub csv_infer_types: bool,
+  pub schemas: BTreeMap<String, PathBuf>,
+  pub not_found_page: PathBuf,
+  pub netlify_toml: PathBuf,
+  pub bind: IpAddr,
+  pub port: Option<u16>,
+  pub port_range: (u16, u16),
+  pub open_browser: bool,
+  pub browser: Option<String>,
+  pub open_page: String,
+  pub https: bool,
+  pub tls_cert: Option<PathBuf>,
+  pub tls_key: Option<PathBuf>,
+  pub cache_root: PathBuf,
+  pub precompress: bool,
+  pub precompress_min_size: u64,
+  pub compress: bool,
+  pub api_dir: PathBuf,
+  pub api_prefix: String,
+  pub proxy: BTreeMap<String, String>,
+  pub form_paths: Vec<String>,
+  pub form_redirect: Option<String>,
+  pub sync: bool,
+  pub throttle: Vec<ThrottleRule>,
+  pub auth_user: Option<String>,
+  pub auth_password: Option<String>,
+  pub auth_token: bool,
+  pub auth_localhost: bool,
+  pub public_paths: Vec<String>,
+  pub access_log_stdout: bool,
+  pub directory_listings: bool,
+  pub base_path: String,
+  pub templates_root: Option<PathBuf>,
+  pub scripts_root: Option<PathBuf>,
+  pub watch: Vec<PathBuf>,
+  pub use_gitignore: bool,
+  pub poll: Option<NonZeroU64>,
 }
 
 impl Config {
@@ -20,9 +67,168 @@ impl Config {
       logs_root,
       output_root,
       debug,
+      site_url: None,
+      timezone: None,
+      csv_infer_types: false,
+      schemas: BTreeMap::new(),
+      not_found_page: PathBuf::from("404.html"),
+      netlify_toml: PathBuf::from("netlify.toml"),
+      bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+      port: None,
+      port_range: (5444, 6000),
+      open_browser: !cfg!(debug_assertions),
+      browser: None,
+      open_page: "/".to_string(),
+      https: false,
+      tls_cert: None,
+      tls_key: None,
+      cache_root: PathBuf::from(".ssb-cache"),
+      precompress: false,
+      precompress_min_size: 1024,
+      compress: true,
+      api_dir: PathBuf::from("_api"),
+      api_prefix: "/api".to_string(),
+      proxy: BTreeMap::new(),
+      form_paths: vec![],
+      form_redirect: None,
+      sync: false,
+      throttle: vec![],
+      auth_user: None,
+      auth_password: None,
+      auth_token: false,
+      auth_localhost: false,
+      public_paths: vec![],
+      access_log_stdout: false,
+      directory_listings: false,
+      base_path: String::new(),
+      templates_root: None,
+      scripts_root: None,
+      watch: vec![],
+      use_gitignore: false,
+      poll: None,
     }
   }
 
+  /// Reads settings from a TOML file. Anything
+  /// not in the file (or the whole file if it
+  /// doesn't exist) falls back to the defaults.
+  pub fn load(config_file: &Path) -> Result<Config> {
+    if config_file.is_file() {
+      let text = fs::read_to_string(config_file)?;
+      toml::from_str(&text).with_context(|| {
+        format!(
+          "Could not parse {}",
+          config_file.display()
+        )
+      })
+    } else {
+      Ok(Config::default())
+    }
+  }
+
+  /// The basic auth user and password. The
+  /// `SSB_AUTH_USER` and `SSB_AUTH_PASSWORD`
+  /// environment variables take precedence over
+  /// the config file so passwords don't have to
+  /// be stored in it.
+  pub fn auth_credentials(
+    &self
+  ) -> Option<(String, String)> {
+    let user = std::env::var("SSB_AUTH_USER")
+      .ok()
+      .or(self.auth_user.clone())?;
+    let password = std::env::var("SSB_AUTH_PASSWORD")
+      .ok()
+      .or(self.auth_password.clone())?;
+    Some((user, password))
+  }
+
+  /// The path the site is deployed under (e.g.
+  /// `/project` for `https://user.github.io/project/`)
+  /// with a leading slash and no trailing one. It's
+  /// empty when the site is at the root.
+  pub fn base_prefix(&self) -> String {
+    let base_path =
+      self.base_path.trim().trim_matches('/');
+    if base_path.is_empty() {
+      String::new()
+    } else {
+      format!("/{}", base_path)
+    }
+  }
+
+  /// Everything that's watched for changes that
+  /// need a rebuild.
+  pub fn watch_roots(&self) -> Vec<PathBuf> {
+    [
+      Some(self.content_root.clone()),
+      self.templates_root.clone(),
+      self.scripts_root.clone(),
+    ]
+    .into_iter()
+    .flatten()
+    .chain(self.watch.iter().cloned())
+    .fold(vec![], |mut roots, root| {
+      if !roots.contains(&root) {
+        roots.push(root);
+      }
+      roots
+    })
+  }
+
+  /// True when the server has to bind to a new
+  /// address to use the other config.
+  pub fn address_changed(
+    &self,
+    other: &Config,
+  ) -> bool {
+    self.bind != other.bind
+      || self.port != other.port
+      || self.port_range != other.port_range
+  }
+
+  /// True when the preview server has to be
+  /// restarted to use the other config.
+  pub fn server_changed(
+    &self,
+    other: &Config,
+  ) -> bool {
+    self.address_changed(other)
+      || self.content_root != other.content_root
+      || self.output_root != other.output_root
+      || self.logs_root != other.logs_root
+      || self.not_found_page != other.not_found_page
+      || self.netlify_toml != other.netlify_toml
+      || self.https != other.https
+      || self.tls_cert != other.tls_cert
+      || self.tls_key != other.tls_key
+      || self.cache_root != other.cache_root
+      || self.compress != other.compress
+      || self.api_dir != other.api_dir
+      || self.api_prefix != other.api_prefix
+      || self.proxy != other.proxy
+      || self.form_paths != other.form_paths
+      || self.form_redirect != other.form_redirect
+      || self.sync != other.sync
+      || self.throttle != other.throttle
+      || self.auth_user != other.auth_user
+      || self.auth_password != other.auth_password
+      || self.auth_token != other.auth_token
+      || self.auth_localhost != other.auth_localhost
+      || self.public_paths != other.public_paths
+      || self.directory_listings
+        != other.directory_listings
+      || self.base_path != other.base_path
+  }
+
+  pub fn certs_dir(&self) -> PathBuf {
+    self.cache_root.join("certs")
+  }
+
+  pub fn forms_logs(&self) -> PathBuf {
+    self.logs_root.join("forms")
+  }
+
   pub fn json_logs(&self) -> PathBuf {
     self.logs_root.join("json")
   }
@@ -31,3 +237,112 @@ impl Config {
     self.logs_root.join("txt")
   }
 }
+
+impl Default for Config {
+  fn default() -> Config {
+    Config::new(
+      PathBuf::from("content"),
+      PathBuf::from("logs"),
+      PathBuf::from("docs"),
+      true,
+    )
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("", None)]
+  #[case("poll = 300", NonZeroU64::new(300))]
+  fn poll_test(
+    #[case] toml: &str,
+    #[case] expected: Option<NonZeroU64>,
+  ) {
+    let config: Config = toml::from_str(toml).unwrap();
+    assert_eq!(expected, config.poll);
+  }
+
+  #[rstest]
+  #[case(None, None, vec![], vec!["content"])]
+  #[case(
+    Some("theme"),
+    Some("scripts"),
+    vec!["notes", "content", "theme"],
+    vec!["content", "theme", "scripts", "notes"]
+  )]
+  fn watch_roots_test(
+    #[case] templates_root: Option<&str>,
+    #[case] scripts_root: Option<&str>,
+    #[case] watch: Vec<&str>,
+    #[case] expected: Vec<&str>,
+  ) {
+    let config = Config {
+      templates_root: templates_root.map(PathBuf::from),
+      scripts_root: scripts_root.map(PathBuf::from),
+      watch: watch
+        .into_iter()
+        .map(PathBuf::from)
+        .collect(),
+      ..Config::default()
+    };
+    assert_eq!(
+      expected
+        .into_iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>(),
+      config.watch_roots()
+    );
+  }
+
+  #[rstest]
+  #[case("", false)]
+  #[case("content_root = \"site\"\nsync = true", false)]
+  #[case("port = 5000", true)]
+  #[case("bind = \"0.0.0.0\"", true)]
+  #[case("port_range = [6000, 6100]", true)]
+  fn address_changed_test(
+    #[case] toml: &str,
+    #[case] expected: bool,
+  ) {
+    let config: Config = toml::from_str(toml).unwrap();
+    assert_eq!(
+      expected,
+      config.address_changed(&Config::default())
+    );
+  }
+
+  #[rstest]
+  #[case("", false)]
+  #[case("csv_infer_types = true", false)]
+  #[case("watch = [\"notes\"]", false)]
+  #[case("port = 5000", true)]
+  #[case("base_path = \"/project\"", true)]
+  #[case("auth_user = \"alfa\"", true)]
+  #[case(
+    "[proxy]\n\"/api\" = \"http://localhost:3000\"",
+    true
+  )]
+  #[case("sync = true", true)]
+  #[case("[[throttle]]\nlatency = 100", true)]
+  fn server_changed_test(
+    #[case] toml: &str,
+    #[case] expected: bool,
+  ) {
+    let config: Config = toml::from_str(toml).unwrap();
+    assert_eq!(
+      expected,
+      config.server_changed(&Config::default())
+    );
+  }
+
+  #[test]
+  fn poll_zero_test() {
+    assert!(
+      toml::from_str::<Config>("poll = 0").is_err()
+    );
+  }
+}
@@ -1,11 +1,17 @@
 pub mod builder;
 pub mod config;
 pub mod logger;
+pub mod mock_api;
+pub mod netlify;
 pub mod server;
+pub mod status;
 pub mod watcher;
 
 pub use builder::*;
 pub use config::*;
 pub use logger::*;
+pub use mock_api::*;
+pub use netlify::*;
 pub use server::*;
+pub use status::*;
 pub use watcher::*;
@@ -1,3 +1,5 @@
+use crate::server::access_log::ACCESS_LOG_TARGET;
+use crate::status::{SharedStatus, StatusLayer};
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fmt::Result;
@@ -10,6 +12,7 @@ use tracing::metadata::LevelFilter;
 use tracing::span;
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::Targets;
 use tracing_subscriber::fmt;
 use tracing_subscriber::fmt::FmtContext;
 use tracing_subscriber::fmt::FormatEvent;
@@ -30,6 +33,8 @@ pub struct Logger {
   json_level: Option<LevelFilter>,
   txt_dir: Option<PathBuf>,
   txt_level: Option<LevelFilter>,
+  status: Option<SharedStatus>,
+  access_log_stdout: bool,
 }
 
 impl Logger {
@@ -42,6 +47,8 @@ impl Logger {
       json_level: None,
       txt_dir: None,
       txt_level: None,
+      status: None,
+      access_log_stdout: false,
     }
   }
 
@@ -54,6 +61,8 @@ impl Logger {
       json_level: None,
       txt_dir: None,
       txt_level: None,
+      status: None,
+      access_log_stdout: false,
     }
   }
 
@@ -101,6 +110,28 @@ impl Logger {
     }
   }
 
+  pub fn with_status(
+    self,
+    status: SharedStatus,
+  ) -> Self {
+    Self {
+      status: Some(status),
+      ..self
+    }
+  }
+
+  /// Requests that aren't 404s only go to the log
+  /// files unless this is turned on.
+  pub fn with_access_log_stdout(
+    self,
+    access_log_stdout: bool,
+  ) -> Self {
+    Self {
+      access_log_stdout,
+      ..self
+    }
+  }
+
   pub fn init(mut self) -> Vec<WorkerGuard> {
     let json_dir_layer = match (&self.json_dir, &self.json_level) {
       (Some(dir), Some(level)) => {
@@ -139,10 +170,22 @@ impl Logger {
 
     let stdout_layer = match self.stdout {
       Some(level) => {
+        let access_level = if self.access_log_stdout {
+          level
+        } else {
+          LevelFilter::WARN.min(level)
+        };
         let layer = fmt::Layer::default()
           .event_format(MiniFormat)
           .with_writer(std::io::stdout)
-          .with_filter(level);
+          .with_filter(
+            Targets::new()
+              .with_default(level)
+              .with_target(
+                ACCESS_LOG_TARGET,
+                access_level,
+              ),
+          );
         Some(layer)
       }
       None => None,
@@ -164,17 +207,22 @@ impl Logger {
         let layer = fmt::Layer::default()
           .event_format(file_layer_format)
           .with_writer(file_writer)
+          .with_ansi(false)
           .with_filter(*level);
         Some(layer)
       }
       _ => None,
     };
 
+    let status_layer =
+      self.status.clone().map(StatusLayer::new);
+
     let subscriber = tracing_subscriber::Registry::default()
       .with(json_dir_layer)
       .with(stderr_layer)
       .with(stdout_layer)
-      .with(txt_dir_layer);
+      .with(txt_dir_layer)
+      .with(status_layer);
 
     tracing::subscriber::set_global_default(subscriber)
       .expect("unable to set global subscriber");
@@ -0,0 +1,294 @@
+use crate::netlify::match_pattern;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+const METHODS: &[&str] = &[
+  "DELETE", "GET", "HEAD", "OPTIONS", "PATCH", "POST",
+  "PUT",
+];
+
+/// Optional response settings for a route from
+/// `_routes.toml` in the API folder.
+#[derive(
+  Clone, Debug, Default, Deserialize, PartialEq,
+)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteSettings {
+  pub status: Option<u16>,
+  pub headers: BTreeMap<String, String>,
+  /// Milliseconds to wait before responding
+  pub latency: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockRoute {
+  pub method: String,
+  pub pattern: String,
+  pub file: PathBuf,
+  pub settings: RouteSettings,
+}
+
+/// The routes made from the files in the API
+/// folder along with anything that couldn't be
+/// set up.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MockApi {
+  pub routes: Vec<MockRoute>,
+  pub errors: Vec<String>,
+}
+
+impl MockApi {
+  /// Loads a route for each file in the API folder.
+  /// Files and folders that start with `_` are
+  /// skipped. Settings under the `"*"` key in
+  /// `_routes.toml` apply to every route.
+  pub fn load(
+    api_dir: &Path,
+    prefix: &str,
+  ) -> MockApi {
+    let mut api = MockApi::default();
+    let settings_path = api_dir.join("_routes.toml");
+    let mut settings: BTreeMap<String, RouteSettings> =
+      match fs::read_to_string(&settings_path) {
+        Ok(text) => match toml::from_str(&text) {
+          Ok(settings) => settings,
+          Err(e) => {
+            api.errors.push(format!(
+              "{}: {}",
+              settings_path.display(),
+              e
+            ));
+            BTreeMap::new()
+          }
+        },
+        Err(_) => BTreeMap::new(),
+      };
+    let defaults =
+      settings.remove("*").unwrap_or_default();
+    let mut files: Vec<String> = WalkDir::new(api_dir)
+      .into_iter()
+      .filter_entry(|entry| {
+        entry.depth() == 0
+          || !entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with('_')
+      })
+      .filter_map(|entry| entry.ok())
+      .filter(|entry| entry.file_type().is_file())
+      .filter_map(|entry| {
+        Some(
+          entry
+            .path()
+            .strip_prefix(api_dir)
+            .ok()?
+            .display()
+            .to_string(),
+        )
+      })
+      .collect();
+    files.sort();
+    for file in files {
+      let (method, pattern) =
+        MockApi::route_for(&file, prefix);
+      let route_settings = settings.remove(&file);
+      api.routes.push(MockRoute {
+        method,
+        pattern,
+        file: api_dir.join(&file),
+        settings: RouteSettings {
+          status: route_settings
+            .as_ref()
+            .and_then(|s| s.status)
+            .or(defaults.status),
+          headers: defaults
+            .headers
+            .clone()
+            .into_iter()
+            .chain(
+              route_settings
+                .as_ref()
+                .map(|s| s.headers.clone())
+                .unwrap_or_default(),
+            )
+            .collect(),
+          latency: route_settings
+            .as_ref()
+            .and_then(|s| s.latency)
+            .or(defaults.latency),
+        },
+      });
+    }
+    api.errors.extend(settings.keys().map(|key| {
+      format!(
+        "{}: no file for [\"{}\"]",
+        settings_path.display(),
+        key
+      )
+    }));
+    // Routes with fewer placeholders win so
+    // `users/me.json` comes before `users/[id].json`
+    api.routes.sort_by_key(|route| {
+      route.pattern.matches("/:").count()
+    });
+    api
+  }
+
+  /// Turns a file path in the API folder into a
+  /// method and route pattern. A leading method
+  /// folder (e.g. `POST/`) sets the method,
+  /// otherwise it's `GET`. `[name]` segments
+  /// become placeholders and the extension and any
+  /// `index` file name are dropped.
+  pub fn route_for(
+    file: &str,
+    prefix: &str,
+  ) -> (String, String) {
+    let mut parts: Vec<&str> = file.split('/').collect();
+    let method = match parts.first() {
+      Some(first)
+        if parts.len() > 1 && METHODS.contains(first) =>
+      {
+        parts.remove(0).to_string()
+      }
+      _ => "GET".to_string(),
+    };
+    if let Some(last) = parts.last_mut() {
+      *last = last
+        .split_once('.')
+        .map_or(*last, |(stem, _)| stem);
+      if *last == "index" {
+        parts.pop();
+      }
+    }
+    let path = parts
+      .iter()
+      .map(|part| {
+        match part
+          .strip_prefix('[')
+          .and_then(|p| p.strip_suffix(']'))
+        {
+          Some(name) => format!(":{}", name),
+          None => part.to_string(),
+        }
+      })
+      .collect::<Vec<_>>()
+      .join("/");
+    (
+      method,
+      format!(
+        "{}/{}",
+        prefix.trim_end_matches('/'),
+        path
+      )
+      .trim_end_matches('/')
+      .to_string(),
+    )
+  }
+
+  /// Finds the route for a request along with the
+  /// values for its placeholders. `HEAD` requests
+  /// use `GET` routes if there's no `HEAD` one.
+  pub fn find(
+    &self,
+    method: &str,
+    path: &str,
+  ) -> Option<(&MockRoute, BTreeMap<String, String>)> {
+    let find_method = |method: &str| {
+      self.routes.iter().find_map(|route| {
+        if route.method != method {
+          return None;
+        }
+        match_pattern(&route.pattern, path)
+          .map(|params| (route, params))
+      })
+    };
+    find_method(method).or_else(|| {
+      (method == "HEAD")
+        .then(|| find_method("GET"))
+        .flatten()
+    })
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("users.json", "GET", "/api/users")]
+  #[case("users/index.json", "GET", "/api/users")]
+  #[case("users/[id].json", "GET", "/api/users/:id")]
+  #[case("POST/users.json", "POST", "/api/users")]
+  #[case(
+    "DELETE/users/[id]/posts/[post].json",
+    "DELETE",
+    "/api/users/:id/posts/:post"
+  )]
+  #[case("index.json", "GET", "/api")]
+  #[case("GET.json", "GET", "/api/GET")]
+  fn route_for_test(
+    #[case] file: &str,
+    #[case] method: &str,
+    #[case] pattern: &str,
+  ) {
+    assert_eq!(
+      (method.to_string(), pattern.to_string()),
+      MockApi::route_for(file, "/api")
+    );
+  }
+
+  fn route(
+    method: &str,
+    pattern: &str,
+  ) -> MockRoute {
+    MockRoute {
+      method: method.to_string(),
+      pattern: pattern.to_string(),
+      file: PathBuf::from(pattern),
+      settings: RouteSettings::default(),
+    }
+  }
+
+  #[rstest]
+  #[case("GET", "/api/users/me", Some("/api/users/me"))]
+  #[case("GET", "/api/users/7", Some("/api/users/:id"))]
+  #[case("HEAD", "/api/users/7", Some("/api/users/:id"))]
+  #[case("POST", "/api/users/7", None)]
+  #[case("GET", "/api/users/7/posts", None)]
+  fn find_test(
+    #[case] method: &str,
+    #[case] path: &str,
+    #[case] expected: Option<&str>,
+  ) {
+    let api = MockApi {
+      routes: vec![
+        route("GET", "/api/users/me"),
+        route("GET", "/api/users/:id"),
+      ],
+      errors: vec![],
+    };
+    assert_eq!(
+      expected,
+      api
+        .find(method, path)
+        .map(|(route, _)| route.pattern.as_str())
+    );
+  }
+
+  #[test]
+  fn find_params_test() {
+    let api = MockApi {
+      routes: vec![route("GET", "/api/users/:id")],
+      errors: vec![],
+    };
+    let (_, params) =
+      api.find("GET", "/api/users/7").unwrap();
+    assert_eq!(Some(&"7".to_string()), params.get("id"));
+  }
+}
@@ -0,0 +1,545 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Redirect {
+  pub from: String,
+  pub to: String,
+  pub status: u16,
+  pub force: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeaderRule {
+  pub path: String,
+  pub values: Vec<(String, String)>,
+}
+
+/// Redirect and header rules from `_redirects`,
+/// `_headers` and `netlify.toml` along with
+/// anything that couldn't be parsed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NetlifyRules {
+  pub redirects: Vec<Redirect>,
+  pub headers: Vec<HeaderRule>,
+  pub errors: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NetlifyToml {
+  #[serde(default)]
+  redirects: Vec<TomlRedirect>,
+  #[serde(default)]
+  headers: Vec<TomlHeaders>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TomlRedirect {
+  from: String,
+  to: String,
+  status: Option<u16>,
+  #[serde(default)]
+  force: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct TomlHeaders {
+  #[serde(rename = "for")]
+  path: String,
+  #[serde(default)]
+  values: BTreeMap<String, String>,
+}
+
+impl NetlifyRules {
+  /// Loads `_redirects` and `_headers` from the
+  /// site folder and `netlify.toml`. Like Netlify,
+  /// `_redirects` rules come before the ones in
+  /// `netlify.toml`.
+  pub fn load(
+    netlify_toml: &Path,
+    site_dir: &Path,
+  ) -> NetlifyRules {
+    let mut rules = NetlifyRules::default();
+    let redirects_path = site_dir.join("_redirects");
+    if let Ok(text) = fs::read_to_string(&redirects_path)
+    {
+      let (redirects, errors) =
+        NetlifyRules::parse_redirects(&text);
+      rules.redirects.extend(redirects);
+      rules.add_errors(&redirects_path, errors);
+    }
+    let headers_path = site_dir.join("_headers");
+    if let Ok(text) = fs::read_to_string(&headers_path) {
+      let (headers, errors) =
+        NetlifyRules::parse_headers(&text);
+      rules.headers.extend(headers);
+      rules.add_errors(&headers_path, errors);
+    }
+    if let Ok(text) = fs::read_to_string(netlify_toml) {
+      match NetlifyRules::parse_toml(&text) {
+        Ok((redirects, headers)) => {
+          rules.redirects.extend(redirects);
+          rules.headers.extend(headers);
+        }
+        Err(e) => rules.add_errors(netlify_toml, vec![e]),
+      }
+    }
+    rules
+  }
+
+  fn add_errors(
+    &mut self,
+    source: &Path,
+    errors: Vec<String>,
+  ) {
+    self.errors.extend(
+      errors
+        .into_iter()
+        .map(|e| format!("{}: {}", source.display(), e)),
+    );
+  }
+
+  pub fn parse_redirects(
+    text: &str
+  ) -> (Vec<Redirect>, Vec<String>) {
+    let mut redirects = vec![];
+    let mut errors = vec![];
+    for (index, line) in text.lines().enumerate() {
+      // `#` only starts a comment at the start of
+      // the line or after whitespace so fragments
+      // like `/new#section` are kept.
+      let parts: Vec<&str> = line
+        .split_whitespace()
+        .take_while(|part| !part.starts_with('#'))
+        .collect();
+      if parts.is_empty() {
+        continue;
+      }
+      if parts.len() < 2 {
+        errors.push(format!(
+          "line {}: expected 'from to [status]'",
+          index + 1
+        ));
+        continue;
+      }
+      if parts.len() > 3 {
+        errors.push(format!(
+          "line {}: query and condition matching isn't supported",
+          index + 1
+        ));
+        continue;
+      }
+      match parts
+        .get(2)
+        .map_or(Ok((301, false)), |status| {
+          parse_status(status)
+        }) {
+        Ok((status, force)) => redirects.push(Redirect {
+          from: parts[0].to_string(),
+          to: parts[1].to_string(),
+          status,
+          force,
+        }),
+        Err(e) => errors.push(format!(
+          "line {}: {}",
+          index + 1,
+          e
+        )),
+      }
+    }
+    (redirects, errors)
+  }
+
+  pub fn parse_headers(
+    text: &str
+  ) -> (Vec<HeaderRule>, Vec<String>) {
+    let mut headers: Vec<HeaderRule> = vec![];
+    let mut errors = vec![];
+    for (index, line) in text.lines().enumerate() {
+      if line.trim().is_empty()
+        || line.trim().starts_with('#')
+      {
+        continue;
+      }
+      if !line.starts_with(char::is_whitespace) {
+        headers.push(HeaderRule {
+          path: line.trim().to_string(),
+          values: vec![],
+        });
+      } else if let Some(rule) = headers.last_mut() {
+        match line.trim().split_once(':') {
+          Some((name, value))
+            if !name.trim().is_empty() =>
+          {
+            rule.values.push((
+              name.trim().to_string(),
+              value.trim().to_string(),
+            ))
+          }
+          _ => errors.push(format!(
+            "line {}: expected 'Name: value'",
+            index + 1
+          )),
+        }
+      } else {
+        errors.push(format!(
+          "line {}: header without a path above it",
+          index + 1
+        ));
+      }
+    }
+    (headers, errors)
+  }
+
+  pub fn parse_toml(
+    text: &str
+  ) -> Result<(Vec<Redirect>, Vec<HeaderRule>), String>
+  {
+    let parsed: NetlifyToml =
+      toml::from_str(text).map_err(|e| e.to_string())?;
+    let redirects = parsed
+      .redirects
+      .into_iter()
+      .map(|redirect| Redirect {
+        from: redirect.from,
+        to: redirect.to,
+        status: redirect.status.unwrap_or(301),
+        force: redirect.force,
+      })
+      .collect();
+    let headers = parsed
+      .headers
+      .into_iter()
+      .map(|headers| HeaderRule {
+        path: headers.path,
+        values: headers.values.into_iter().collect(),
+      })
+      .collect();
+    Ok((redirects, headers))
+  }
+
+  /// Returns the first redirect that matches the
+  /// path along with its target after filling in
+  /// placeholders and splats.
+  pub fn find_redirect(
+    &self,
+    path: &str,
+  ) -> Option<(&Redirect, String)> {
+    self.redirects.iter().find_map(|redirect| {
+      match_pattern(&redirect.from, path).map(
+        |captures| {
+          (
+            redirect,
+            fill_placeholders(&redirect.to, &captures),
+          )
+        },
+      )
+    })
+  }
+
+  pub fn headers_for(
+    &self,
+    path: &str,
+  ) -> Vec<(String, String)> {
+    self
+      .headers
+      .iter()
+      .filter(|rule| {
+        match_pattern(&rule.path, path).is_some()
+      })
+      .flat_map(|rule| rule.values.clone())
+      .collect()
+  }
+}
+
+fn parse_status(
+  status: &str
+) -> Result<(u16, bool), String> {
+  let force = status.ends_with('!');
+  match status.trim_end_matches('!').parse::<u16>() {
+    Ok(code) if (100..600).contains(&code) => {
+      Ok((code, force))
+    }
+    _ => Err(format!("invalid status '{}'", status)),
+  }
+}
+
+/// Matches a path against a Netlify style pattern.
+/// `:name` matches a single segment and a trailing
+/// `*` matches everything after it (as `splat`).
+/// Trailing slashes are ignored like they are on
+/// Netlify.
+pub fn match_pattern(
+  pattern: &str,
+  path: &str,
+) -> Option<BTreeMap<String, String>> {
+  let pattern_parts: Vec<&str> = pattern
+    .trim_matches('/')
+    .split('/')
+    .filter(|part| !part.is_empty())
+    .collect();
+  let path_parts: Vec<&str> = path
+    .trim_matches('/')
+    .split('/')
+    .filter(|part| !part.is_empty())
+    .collect();
+  let mut captures = BTreeMap::new();
+  for (index, pattern_part) in
+    pattern_parts.iter().enumerate()
+  {
+    if *pattern_part == "*" {
+      captures.insert(
+        "splat".to_string(),
+        path_parts[index.min(path_parts.len())..]
+          .join("/"),
+      );
+      return Some(captures);
+    }
+    let path_part = path_parts.get(index)?;
+    if let Some(name) = pattern_part.strip_prefix(':') {
+      captures
+        .insert(name.to_string(), path_part.to_string());
+    } else if pattern_part != path_part {
+      return None;
+    }
+  }
+  (pattern_parts.len() == path_parts.len())
+    .then_some(captures)
+}
+
+fn fill_placeholders(
+  to: &str,
+  captures: &BTreeMap<String, String>,
+) -> String {
+  let mut names: Vec<&String> = captures.keys().collect();
+  names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+  names.iter().fold(to.to_string(), |target, name| {
+    target
+      .replace(&format!(":{}", name), &captures[*name])
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("/old", "/old", true)]
+  #[case("/old", "/old/", true)]
+  #[case("/old/", "/old", true)]
+  #[case("/old", "/older", false)]
+  #[case("/blog/*", "/blog/a/b", true)]
+  #[case("/blog/*", "/blog", true)]
+  #[case("/*", "/", true)]
+  #[case("/news/:year/:slug", "/news/2025/hello", true)]
+  #[case("/news/:year/:slug", "/news/2025", false)]
+  fn match_pattern_test(
+    #[case] pattern: &str,
+    #[case] path: &str,
+    #[case] expected: bool,
+  ) {
+    assert_eq!(
+      expected,
+      match_pattern(pattern, path).is_some()
+    );
+  }
+
+  #[rstest]
+  #[case(
+    "/blog/* /posts/:splat 301",
+    "/blog/a/b",
+    "/posts/a/b"
+  )]
+  #[case(
+    "/news/:year/:slug /archive/:year/:slug.html 200",
+    "/news/2025/hello",
+    "/archive/2025/hello.html"
+  )]
+  #[case(
+    "/app/* /index.html 200",
+    "/app/x",
+    "/index.html"
+  )]
+  fn find_redirect_test(
+    #[case] rules: &str,
+    #[case] path: &str,
+    #[case] expected: &str,
+  ) {
+    let (redirects, errors) =
+      NetlifyRules::parse_redirects(rules);
+    assert_eq!(Vec::<String>::new(), errors);
+    let rules = NetlifyRules {
+      redirects,
+      ..NetlifyRules::default()
+    };
+    let (_, target) = rules.find_redirect(path).unwrap();
+    assert_eq!(expected, target);
+  }
+
+  #[test]
+  fn parse_redirects_test() {
+    let text = "# comment\n\n/old /new\n/a /b 302!\n/c /d 200 # inline\n";
+    let (redirects, errors) =
+      NetlifyRules::parse_redirects(text);
+    assert_eq!(Vec::<String>::new(), errors);
+    assert_eq!(
+      vec![
+        Redirect {
+          from: "/old".to_string(),
+          to: "/new".to_string(),
+          status: 301,
+          force: false
+        },
+        Redirect {
+          from: "/a".to_string(),
+          to: "/b".to_string(),
+          status: 302,
+          force: true
+        },
+        Redirect {
+          from: "/c".to_string(),
+          to: "/d".to_string(),
+          status: 200,
+          force: false
+        },
+      ],
+      redirects
+    );
+  }
+
+  #[rstest]
+  #[case("/old /new#section 301", "/new#section", 301)]
+  #[case(
+    "/old /new#section # moved",
+    "/new#section",
+    301
+  )]
+  #[case("/old /new#a 302 #moved", "/new#a", 302)]
+  #[case("  /old /new # moved", "/new", 301)]
+  fn parse_redirects_fragment_test(
+    #[case] text: &str,
+    #[case] to: &str,
+    #[case] status: u16,
+  ) {
+    let (redirects, errors) =
+      NetlifyRules::parse_redirects(text);
+    assert_eq!(Vec::<String>::new(), errors);
+    assert_eq!(
+      vec![Redirect {
+        from: "/old".to_string(),
+        to: to.to_string(),
+        status,
+        force: false
+      }],
+      redirects
+    );
+  }
+
+  #[rstest]
+  #[case(
+    "/only-from\n",
+    "line 1: expected 'from to [status]'"
+  )]
+  #[case(
+    "/a /b\n/c /d 9999\n",
+    "line 2: invalid status '9999'"
+  )]
+  #[case(
+    "/a /b 301 Country=us\n",
+    "line 1: query and condition matching isn't supported"
+  )]
+  fn parse_redirects_error_test(
+    #[case] text: &str,
+    #[case] expected: &str,
+  ) {
+    let (_, errors) = NetlifyRules::parse_redirects(text);
+    assert_eq!(vec![expected.to_string()], errors);
+  }
+
+  #[test]
+  fn parse_headers_test() {
+    let text = "/*\n  X-Frame-Options: DENY\n\n/styles/*\n  Cache-Control: max-age=60\n  X-Test: a: b\n";
+    let (headers, errors) =
+      NetlifyRules::parse_headers(text);
+    assert_eq!(Vec::<String>::new(), errors);
+    let rules = NetlifyRules {
+      headers,
+      ..NetlifyRules::default()
+    };
+    assert_eq!(
+      vec![
+        (
+          "X-Frame-Options".to_string(),
+          "DENY".to_string()
+        ),
+        (
+          "Cache-Control".to_string(),
+          "max-age=60".to_string()
+        ),
+        ("X-Test".to_string(), "a: b".to_string()),
+      ],
+      rules.headers_for("/styles/main.css")
+    );
+  }
+
+  #[rstest]
+  #[case(
+    "  X-Early: yes\n",
+    "line 1: header without a path above it"
+  )]
+  #[case(
+    "/*\n  not a header\n",
+    "line 2: expected 'Name: value'"
+  )]
+  fn parse_headers_error_test(
+    #[case] text: &str,
+    #[case] expected: &str,
+  ) {
+    let (_, errors) = NetlifyRules::parse_headers(text);
+    assert_eq!(vec![expected.to_string()], errors);
+  }
+
+  #[test]
+  fn parse_toml_test() {
+    let text = r#"
+[build]
+  publish = "docs/"
+
+[[redirects]]
+  from = "/old/*"
+  to = "/new/:splat"
+  status = 302
+  force = true
+
+[[headers]]
+  for = "/*"
+  [headers.values]
+    X-Frame-Options = "DENY"
+"#;
+    let (redirects, headers) =
+      NetlifyRules::parse_toml(text).unwrap();
+    assert_eq!(
+      vec![Redirect {
+        from: "/old/*".to_string(),
+        to: "/new/:splat".to_string(),
+        status: 302,
+        force: true
+      }],
+      redirects
+    );
+    assert_eq!(
+      vec![HeaderRule {
+        path: "/*".to_string(),
+        values: vec![(
+          "X-Frame-Options".to_string(),
+          "DENY".to_string()
+        )]
+      }],
+      headers
+    );
+  }
+}
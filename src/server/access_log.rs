@@ -0,0 +1,164 @@
+use crate::server::RemoteAddr;
+use crate::server::auth::redact_token;
+use crate::status::SharedStatus;
+use axum::body::Body;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::StatusCode;
+use axum::http::header::{REFERER, USER_AGENT};
+use axum::middleware::Next;
+use axum::response::Response;
+use futures_util::StreamExt;
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// The tracing target requests are logged with so
+/// the logger can send them to different places
+/// than the builder's messages.
+pub const ACCESS_LOG_TARGET: &str = "ssb_access";
+
+#[derive(Clone, Debug)]
+pub struct AccessLogState {
+  pub status: SharedStatus,
+}
+
+/// Logs each request once its response has been
+/// sent (or the connection is closed) so `bytes`
+/// and `duration_ms` cover the whole response.
+/// 404s are logged as warnings and counted in the
+/// status for the missing paths summary.
+pub async fn log_requests(
+  State(state): State<AccessLogState>,
+  request: Request,
+  next: Next,
+) -> Response {
+  // Read before the request is passed on.
+  let (mut entry, referer) = {
+    let header = |name| {
+      request
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+    };
+    let entry = AccessLogEntry {
+      method: request.method().to_string(),
+      path: request
+        .uri()
+        .path_and_query()
+        .map(|path| redact_token(path.as_str()))
+        .unwrap_or("/".to_string()),
+      status: StatusCode::OK,
+      bytes: 0,
+      user_agent: header(USER_AGENT),
+      remote: request
+        .extensions()
+        .get::<ConnectInfo<RemoteAddr>>()
+        .map(|ConnectInfo(RemoteAddr(address))| {
+          address.ip().to_canonical().to_string()
+        }),
+      started: Instant::now(),
+    };
+    (entry, header(REFERER))
+  };
+  let response = next.run(request).await;
+  entry.status = response.status();
+  if entry.status == StatusCode::NOT_FOUND {
+    state
+      .status
+      .lock()
+      .unwrap()
+      .add_not_found(request_path(&entry.path), referer);
+  }
+  response.map(|body| {
+    Body::from_stream(body.into_data_stream().map(
+      move |chunk| {
+        if let Ok(bytes) = &chunk {
+          entry.add_bytes(bytes.len());
+        }
+        chunk
+      },
+    ))
+  })
+}
+
+fn request_path(path_and_query: &str) -> &str {
+  path_and_query
+    .split_once('?')
+    .map(|(path, _)| path)
+    .unwrap_or(path_and_query)
+}
+
+// Logged when it's dropped along with the
+// response body.
+struct AccessLogEntry {
+  method: String,
+  path: String,
+  status: StatusCode,
+  bytes: u64,
+  user_agent: Option<String>,
+  remote: Option<String>,
+  started: Instant,
+}
+
+impl AccessLogEntry {
+  fn add_bytes(
+    &mut self,
+    bytes: usize,
+  ) {
+    self.bytes += bytes as u64;
+  }
+}
+
+impl Drop for AccessLogEntry {
+  fn drop(&mut self) {
+    let duration_ms =
+      self.started.elapsed().as_millis() as u64;
+    let user_agent =
+      self.user_agent.as_deref().unwrap_or("-");
+    let remote = self.remote.as_deref().unwrap_or("-");
+    if self.status == StatusCode::NOT_FOUND {
+      warn!(
+        target: ACCESS_LOG_TARGET,
+        method = self.method,
+        path = self.path,
+        status = self.status.as_u16(),
+        bytes = self.bytes,
+        duration_ms,
+        user_agent,
+        remote,
+        "Not found"
+      );
+    } else {
+      info!(
+        target: ACCESS_LOG_TARGET,
+        method = self.method,
+        path = self.path,
+        status = self.status.as_u16(),
+        bytes = self.bytes,
+        duration_ms,
+        user_agent,
+        remote,
+      );
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case(
+    "/styles/main.css?v=1a2b3c4d",
+    "/styles/main.css"
+  )]
+  #[case("/about/", "/about/")]
+  fn request_path_test(
+    #[case] path_and_query: &str,
+    #[case] expected: &str,
+  ) {
+    assert_eq!(expected, request_path(path_and_query));
+  }
+}
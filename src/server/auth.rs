@@ -0,0 +1,553 @@
+use crate::config::Config;
+use crate::server::RemoteAddr;
+use crate::server::introspection::INTROSPECTION_PREFIX;
+use axum::Json;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::header::{
+  AUTHORIZATION, COOKIE, SET_COOKIE, WWW_AUTHENTICATE,
+};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use globset::{Glob, GlobMatcher};
+use serde_json::json;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+/// The query parameter a token link uses
+pub const TOKEN_PARAM: &str = "ssb_token";
+
+/// The cookie a browser gets once it has used a
+/// token link
+pub const SESSION_COOKIE: &str = "ssb_session";
+
+/// Headers a proxy or tunnel adds. Requests with
+/// them aren't treated as coming from this machine
+/// even when the connection is.
+const FORWARDED_HEADERS: &[&str] = &[
+  "forwarded",
+  "x-forwarded-for",
+  "x-forwarded-host",
+  "x-real-ip",
+];
+
+/// Who can see the preview. Requests to public
+/// paths are always let in as are ones from this
+/// machine unless `auth_localhost` is on. Everyone
+/// else needs the basic auth user and password or
+/// a session from a token link. Each token link
+/// works once.
+#[derive(Clone, Debug)]
+pub struct AuthState {
+  credentials: Option<String>,
+  local_bypass: bool,
+  base_path: String,
+  public_paths: Vec<GlobMatcher>,
+  tokens: Arc<Mutex<HashSet<String>>>,
+  sessions: Arc<Mutex<HashSet<String>>>,
+  urls: Vec<String>,
+}
+
+impl AuthState {
+  /// Returns `None` when neither basic auth nor
+  /// token links are set up.
+  pub fn new(
+    config: &Config,
+    urls: Vec<String>,
+  ) -> Option<AuthState> {
+    let credentials = basic_credentials(config);
+    if credentials.is_none() && !config.auth_token {
+      return None;
+    }
+    let public_paths = config
+      .public_paths
+      .iter()
+      .filter_map(|path| match Glob::new(path) {
+        Ok(glob) => Some(glob.compile_matcher()),
+        Err(e) => {
+          error!("Invalid public path {}: {}", path, e);
+          None
+        }
+      })
+      .collect();
+    Some(AuthState {
+      credentials,
+      local_bypass: !config.auth_localhost,
+      base_path: config.base_prefix(),
+      public_paths,
+      tokens: Arc::new(Mutex::new(HashSet::new())),
+      sessions: Arc::new(Mutex::new(HashSet::new())),
+      urls,
+    })
+  }
+
+  /// Says how other devices can get in and prints
+  /// a token link when they're turned on.
+  pub fn log_access(
+    &self,
+    token_link: bool,
+  ) {
+    if self.credentials.is_some() {
+      info!("Other devices need the basic auth password");
+    }
+    if token_link {
+      self.token_links().iter().for_each(|link| {
+        info!("One-time link: {}", link);
+      });
+    }
+  }
+
+  /// Makes a new token and returns a link with it
+  /// for each of the server's URLs. Links to
+  /// localhost are left out if there are others
+  /// since they only work on this machine.
+  pub fn token_links(&self) -> Vec<String> {
+    let token = random_hex();
+    self.tokens.lock().unwrap().insert(token.clone());
+    let urls = self
+      .urls
+      .iter()
+      .filter(|url| !url.contains("://localhost:"))
+      .collect::<Vec<_>>();
+    let urls = if urls.is_empty() {
+      self.urls.iter().collect()
+    } else {
+      urls
+    };
+    urls
+      .iter()
+      .map(|url| {
+        format!("{}?{}={}", url, TOKEN_PARAM, token)
+      })
+      .collect()
+  }
+
+  // Forwarded requests come in over loopback
+  // from tunnels and proxies so they aren't
+  // local.
+  fn is_local(
+    &self,
+    address: IpAddr,
+    headers: &HeaderMap,
+  ) -> bool {
+    self.local_bypass
+      && address.to_canonical().is_loopback()
+      && !FORWARDED_HEADERS
+        .iter()
+        .any(|name| headers.contains_key(*name))
+  }
+
+  // Public paths are written the way they are in
+  // the site so the base path is taken off first.
+  fn is_public(
+    &self,
+    path: &str,
+  ) -> bool {
+    let path = path
+      .strip_prefix(&self.base_path)
+      .filter(|rest| rest.starts_with('/'))
+      .unwrap_or(path);
+    self
+      .public_paths
+      .iter()
+      .any(|matcher| matcher.is_match(path))
+  }
+
+  fn has_credentials(
+    &self,
+    headers: &HeaderMap,
+  ) -> bool {
+    self.credentials.as_ref().is_some_and(|credentials| {
+      headers.get(AUTHORIZATION).is_some_and(|value| {
+        constant_time_eq(
+          value.as_bytes(),
+          credentials.as_bytes(),
+        )
+      })
+    })
+  }
+
+  fn has_session(
+    &self,
+    headers: &HeaderMap,
+  ) -> bool {
+    cookie_value(headers, SESSION_COOKIE).is_some_and(
+      |session| {
+        self.sessions.lock().unwrap().contains(&session)
+      },
+    )
+  }
+
+  // Trades a token for a new session. The token
+  // can't be used again.
+  fn use_token(
+    &self,
+    token: &str,
+  ) -> Option<String> {
+    if !self.tokens.lock().unwrap().remove(token) {
+      return None;
+    }
+    let session = random_hex();
+    self.sessions.lock().unwrap().insert(session.clone());
+    Some(session)
+  }
+}
+
+/// Turns away requests from other devices that
+/// don't have the password or a session. A request
+/// with an unused token gets a session cookie and
+/// is redirected to the same URL without the token.
+pub async fn require_auth(
+  State(state): State<Option<AuthState>>,
+  ConnectInfo(RemoteAddr(address)): ConnectInfo<
+    RemoteAddr,
+  >,
+  request: Request,
+  next: Next,
+) -> Response {
+  let Some(state) = state else {
+    return next.run(request).await;
+  };
+  let path = request.uri().path().to_string();
+  let token = request.uri().query().and_then(|query| {
+    query.split('&').find_map(|pair| {
+      pair
+        .strip_prefix(TOKEN_PARAM)
+        .and_then(|rest| rest.strip_prefix('='))
+        .map(|token| token.to_string())
+    })
+  });
+  if let Some(token) = token {
+    return match state.use_token(&token) {
+      Some(session) => {
+        info!("Token link used from {}", address.ip());
+        let location = without_token(
+          request
+            .uri()
+            .path_and_query()
+            .map(|path| path.as_str())
+            .unwrap_or("/"),
+        );
+        (
+          [(
+            SET_COOKIE,
+            format!(
+              "{}={}; Path=/; HttpOnly; SameSite=Lax",
+              SESSION_COOKIE, session
+            ),
+          )],
+          Redirect::to(&location),
+        )
+          .into_response()
+      }
+      None => unauthorized(
+        &state,
+        "This link has already been used",
+      ),
+    };
+  }
+  let allowed = state
+    .is_local(address.ip(), request.headers())
+    || state.is_public(&path)
+    || state.has_session(request.headers())
+    || state.has_credentials(request.headers());
+  if !allowed {
+    return unauthorized(
+      &state,
+      "This preview needs a password or a link",
+    );
+  }
+  if path == format!("{}/token", INTROSPECTION_PREFIX) {
+    return Json(json!({ "links": state.token_links() }))
+      .into_response();
+  }
+  next.run(request).await
+}
+
+fn unauthorized(
+  state: &AuthState,
+  message: &'static str,
+) -> Response {
+  let mut response =
+    (StatusCode::UNAUTHORIZED, message).into_response();
+  if state.credentials.is_some() {
+    response.headers_mut().insert(
+      WWW_AUTHENTICATE,
+      r#"Basic realm="Preview", charset="UTF-8""#
+        .parse()
+        .unwrap(),
+    );
+  }
+  response
+}
+
+fn cookie_value(
+  headers: &HeaderMap,
+  name: &str,
+) -> Option<String> {
+  headers
+    .get_all(COOKIE)
+    .iter()
+    .filter_map(|value| value.to_str().ok())
+    .flat_map(|value| value.split(';'))
+    .find_map(|cookie| {
+      let (key, value) = cookie.trim().split_once('=')?;
+      (key == name).then(|| value.to_string())
+    })
+}
+
+/// Removes the token parameter from a path and
+/// query.
+pub fn without_token(path_and_query: &str) -> String {
+  match path_and_query.split_once('?') {
+    Some((path, query)) => {
+      let query = query
+        .split('&')
+        .filter(|pair| {
+          pair.split('=').next() != Some(TOKEN_PARAM)
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+      if query.is_empty() {
+        path.to_string()
+      } else {
+        format!("{}?{}", path, query)
+      }
+    }
+    None => path_and_query.to_string(),
+  }
+}
+
+/// The `Authorization` header value for the
+/// configured basic auth user and password.
+pub fn basic_credentials(
+  config: &Config
+) -> Option<String> {
+  config.auth_credentials().map(|(user, password)| {
+    format!(
+      "Basic {}",
+      STANDARD.encode(format!("{}:{}", user, password))
+    )
+  })
+}
+
+/// Replaces the token in a path and query so it
+/// doesn't end up in the logs.
+pub fn redact_token(path_and_query: &str) -> String {
+  match path_and_query.split_once('?') {
+    Some((path, query)) => {
+      let query = query
+        .split('&')
+        .map(|pair| {
+          if pair.split('=').next() == Some(TOKEN_PARAM) {
+            format!("{}=REDACTED", TOKEN_PARAM)
+          } else {
+            pair.to_string()
+          }
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+      format!("{}?{}", path, query)
+    }
+    None => path_and_query.to_string(),
+  }
+}
+
+// Compares every byte so the time taken doesn't
+// show how much of a guess was right.
+fn constant_time_eq(
+  a: &[u8],
+  b: &[u8],
+) -> bool {
+  a.len() == b.len()
+    && a
+      .iter()
+      .zip(b)
+      .fold(0, |diff, (a, b)| diff | (a ^ b))
+      == 0
+}
+
+fn random_hex() -> String {
+  let mut bytes = [0u8; 16];
+  getrandom::fill(&mut bytes)
+    .expect("Could not get random bytes");
+  bytes
+    .iter()
+    .map(|byte| format!("{:02x}", byte))
+    .collect()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use axum::http::HeaderName;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn auth_state() -> AuthState {
+    let config = Config {
+      auth_user: Some("alfa".to_string()),
+      auth_password: Some("bravo".to_string()),
+      auth_token: true,
+      public_paths: vec!["/favicons/**".to_string()],
+      ..Config::default()
+    };
+    AuthState::new(
+      &config,
+      vec![
+        "http://localhost:5444/".to_string(),
+        "http://192.168.1.20:5444/".to_string(),
+      ],
+    )
+    .unwrap()
+  }
+
+  #[rstest]
+  #[case("/?ssb_token=abc", "/")]
+  #[case(
+    "/about/?a=1&ssb_token=abc&b=2",
+    "/about/?a=1&b=2"
+  )]
+  #[case("/about/?a=1", "/about/?a=1")]
+  #[case("/about/", "/about/")]
+  fn without_token_test(
+    #[case] path_and_query: &str,
+    #[case] expected: &str,
+  ) {
+    assert_eq!(expected, without_token(path_and_query));
+  }
+
+  #[test]
+  fn token_is_used_once_test() {
+    let state = auth_state();
+    let links = state.token_links();
+    assert_eq!(1, links.len());
+    let token = links[0].split('=').next_back().unwrap();
+    let session = state.use_token(token).unwrap();
+    assert_eq!(None, state.use_token(token));
+    let mut headers = HeaderMap::new();
+    headers.insert(
+      COOKIE,
+      format!(
+        "theme=dark; {}={}",
+        SESSION_COOKIE, session
+      )
+      .parse()
+      .unwrap(),
+    );
+    assert!(state.has_session(&headers));
+  }
+
+  #[rstest]
+  #[case("Basic YWxmYTpicmF2bw==", true)]
+  #[case("Basic YWxmYTpjaGFybGll", false)]
+  fn has_credentials_test(
+    #[case] authorization: &str,
+    #[case] expected: bool,
+  ) {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+      AUTHORIZATION,
+      authorization.parse().unwrap(),
+    );
+    assert_eq!(
+      expected,
+      auth_state().has_credentials(&headers)
+    );
+  }
+
+  #[rstest]
+  #[case("/?ssb_token=abc", "/?ssb_token=REDACTED")]
+  #[case(
+    "/about/?a=1&ssb_token=abc",
+    "/about/?a=1&ssb_token=REDACTED"
+  )]
+  #[case("/about/?a=1", "/about/?a=1")]
+  #[case("/about/", "/about/")]
+  fn redact_token_test(
+    #[case] path_and_query: &str,
+    #[case] expected: &str,
+  ) {
+    assert_eq!(expected, redact_token(path_and_query));
+  }
+
+  #[rstest]
+  #[case(b"Basic abc", b"Basic abc", true)]
+  #[case(b"Basic abc", b"Basic abd", false)]
+  #[case(b"Basic abc", b"Basic ab", false)]
+  fn constant_time_eq_test(
+    #[case] a: &[u8],
+    #[case] b: &[u8],
+    #[case] expected: bool,
+  ) {
+    assert_eq!(expected, constant_time_eq(a, b));
+  }
+
+  #[rstest]
+  #[case("127.0.0.1", None, false, true)]
+  #[case("::ffff:127.0.0.1", None, false, true)]
+  #[case("192.168.1.20", None, false, false)]
+  #[case(
+    "127.0.0.1",
+    Some("x-forwarded-for"),
+    false,
+    false
+  )]
+  #[case("127.0.0.1", Some("forwarded"), false, false)]
+  #[case("127.0.0.1", None, true, false)]
+  fn is_local_test(
+    #[case] address: &str,
+    #[case] header: Option<&str>,
+    #[case] auth_localhost: bool,
+    #[case] expected: bool,
+  ) {
+    let state = AuthState {
+      local_bypass: !auth_localhost,
+      ..auth_state()
+    };
+    let mut headers = HeaderMap::new();
+    if let Some(header) = header {
+      headers.insert(
+        HeaderName::from_bytes(header.as_bytes())
+          .unwrap(),
+        "1.2.3.4".parse().unwrap(),
+      );
+    }
+    assert_eq!(
+      expected,
+      state.is_local(address.parse().unwrap(), &headers)
+    );
+  }
+
+  #[rstest]
+  #[case("/favicons/icon.png", true)]
+  #[case("/about/", false)]
+  fn is_public_test(
+    #[case] path: &str,
+    #[case] expected: bool,
+  ) {
+    assert_eq!(expected, auth_state().is_public(path));
+  }
+
+  #[rstest]
+  #[case("/project/favicons/icon.png", true)]
+  #[case("/project/about/", false)]
+  #[case("/projects/favicons/icon.png", false)]
+  fn is_public_base_path_test(
+    #[case] path: &str,
+    #[case] expected: bool,
+  ) {
+    let config = Config {
+      auth_token: true,
+      public_paths: vec!["/favicons/**".to_string()],
+      base_path: "project".to_string(),
+      ..Config::default()
+    };
+    let state = AuthState::new(&config, vec![]).unwrap();
+    assert_eq!(expected, state.is_public(path));
+  }
+}
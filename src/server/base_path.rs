@@ -0,0 +1,107 @@
+use crate::builder::utils::with_base_path;
+use crate::server::passthrough::Passthrough;
+use axum::extract::{Request, State};
+use axum::http::header::LOCATION;
+use axum::http::{HeaderValue, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
+
+/// Serves the site under its base path the way
+/// it's deployed. The `passthrough` prefixes stay
+/// at the root.
+#[derive(Clone, Debug)]
+pub struct BasePathState {
+  pub base_path: String,
+  pub passthrough: Passthrough,
+}
+
+/// Strips the base path from requests for the
+/// site and adds it back to redirects. `/` goes to
+/// the base path. Other paths outside it are 404s
+/// since they'd be broken when deployed.
+pub async fn mount_base_path(
+  State(state): State<BasePathState>,
+  mut request: Request,
+  next: Next,
+) -> Response {
+  let base_path = state.base_path.as_str();
+  let path = request.uri().path().to_string();
+  if base_path.is_empty()
+    || state.passthrough.contains(&path)
+  {
+    return next.run(request).await;
+  }
+  if path == "/" || path == base_path {
+    return Redirect::temporary(&format!(
+      "{}/",
+      base_path
+    ))
+    .into_response();
+  }
+  let Some(uri) =
+    strip_base_path(base_path, request.uri())
+  else {
+    return (
+      StatusCode::NOT_FOUND,
+      format!(
+        "{} is outside the base path {}/",
+        path, base_path
+      ),
+    )
+      .into_response();
+  };
+  *request.uri_mut() = uri;
+  let mut response = next.run(request).await;
+  if let Some(location) = response
+    .headers()
+    .get(LOCATION)
+    .and_then(|location| location.to_str().ok())
+    .map(|location| with_base_path(base_path, location))
+    .and_then(|location| {
+      HeaderValue::from_str(&location).ok()
+    })
+  {
+    response.headers_mut().insert(LOCATION, location);
+  }
+  response
+}
+
+/// The URI with the base path taken off the front
+/// or `None` if it's not under the base path.
+pub fn strip_base_path(
+  base_path: &str,
+  uri: &Uri,
+) -> Option<Uri> {
+  let path = uri.path().strip_prefix(base_path)?;
+  if !path.starts_with('/') {
+    return None;
+  }
+  let path_and_query = match uri.query() {
+    Some(query) => format!("{}?{}", path, query),
+    None => path.to_string(),
+  };
+  path_and_query.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("/project/", Some("/"))]
+  #[case("/project/about/?a=1", Some("/about/?a=1"))]
+  #[case("/projects/", None)]
+  #[case("/about/", None)]
+  fn strip_base_path_test(
+    #[case] uri: &str,
+    #[case] expected: Option<&str>,
+  ) {
+    assert_eq!(
+      expected.map(|uri| uri.to_string()),
+      strip_base_path("/project", &uri.parse().unwrap())
+        .map(|uri| uri.to_string())
+    );
+  }
+}
@@ -0,0 +1,97 @@
+// Added to pages by the preview server in place
+// of the live reload script. It waits on the same
+// long poll then asks what changed. When a build
+// only changes pages and stylesheets, pages that
+// changed reload and the rest swap in the new
+// stylesheets. Other changes reload every page.
+(() => {
+  const inputs = document.currentScript.dataset;
+  const longPoll = `${inputs.liveReload}/long-poll`;
+  const backUp = `${inputs.liveReload}/back-up`;
+  let update = Number(inputs.update);
+
+  addEventListener("pageshow", () => {
+    const controller = new AbortController();
+    let unloaded = false;
+    addEventListener("beforeunload", () => {
+      unloaded = true;
+      controller.abort();
+    });
+    const listen = () => {
+      fetch(longPoll, { cache: "no-store", signal: controller.signal })
+        .then((response) => response.text())
+        .then(changes)
+        .catch(() => {
+          if (!unloaded) retry();
+        });
+    };
+
+    function changes() {
+      return fetch(`/__ssb/updates?since=${update}`, { cache: "no-store" })
+        .then((response) => {
+          if (!response.ok) return Promise.reject();
+          return response.json();
+        })
+        .then((event) => {
+          update = event.id;
+          if (event.type === "reload" || pageChanged(event.pages)) {
+            location.reload();
+          } else {
+            swapCss(event.stylesheets);
+            listen();
+          }
+        });
+    }
+
+    listen();
+  });
+
+  // Waits for the server to come back (e.g. after
+  // a restart) and then reloads.
+  function retry() {
+    const controller = new AbortController();
+    setTimeout(() => controller.abort(), 500);
+    fetch(backUp, { cache: "no-store", signal: controller.signal })
+      .then((response) => {
+        if (!response.ok) return Promise.reject();
+        location.reload();
+      })
+      .catch(() => setTimeout(retry, 1000));
+  }
+
+  function pageChanged(pages) {
+    const path = decodeURI(location.pathname);
+    return [path, `${path}index.html`, `${path}/index.html`]
+      .some((page) => pages.includes(page));
+  }
+
+  // Replaces each changed stylesheet with a fresh
+  // copy and removes the old one once it loads so
+  // the page doesn't flash. If none of the page's
+  // links changed (e.g. the change was to a file
+  // that's imported) all of them are refreshed.
+  function swapCss(paths) {
+    if (paths.length === 0) {
+      return;
+    }
+    const links = [
+      ...document.querySelectorAll('link[rel="stylesheet"]'),
+    ].filter((link) => {
+      return new URL(link.href).origin === location.origin;
+    });
+    const changed = links.filter((link) => {
+      return paths.includes(new URL(link.href).pathname);
+    });
+    (changed.length > 0 ? changed : links).forEach(swapLink);
+  }
+
+  function swapLink(link) {
+    const url = new URL(link.href);
+    url.searchParams.set("ssb", Date.now());
+    const fresh = link.cloneNode();
+    fresh.href = url.href;
+    fresh.addEventListener("load", () => link.remove());
+    fresh.addEventListener("error", () => fresh.remove());
+    link.after(fresh);
+  }
+})();
@@ -0,0 +1,352 @@
+use crate::server::introspection::INTROSPECTION_PREFIX;
+use crate::server::passthrough::LIVE_RELOAD_PREFIX;
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::extract::{Query, Request, State};
+use axum::http::StatusCode;
+use axum::http::header::{
+  CACHE_CONTROL, CONTENT_ENCODING, CONTENT_LENGTH,
+  CONTENT_TYPE,
+};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use axum::routing::get;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// How many builds' changes are kept for pages
+/// that check in late
+pub const RECENT_UPDATES: usize = 16;
+
+/// What a build changed for open pages.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientEvent {
+  /// Open pages listed in `pages` reload. Others
+  /// swap the listed stylesheets in place. Both
+  /// are URL paths of files in the output folder.
+  Update {
+    stylesheets: Vec<String>,
+    pages: Vec<String>,
+  },
+  /// Every open page reloads
+  Reload,
+}
+
+/// The changes from recent builds. After adding
+/// one the builder triggers live reload and pages
+/// ask for the changes since the last build they
+/// saw so the live reload request is the only one
+/// they keep open.
+#[derive(Debug, Default)]
+pub struct ClientUpdates {
+  latest: u64,
+  recent: VecDeque<(u64, ClientEvent)>,
+}
+
+impl ClientUpdates {
+  pub fn push(
+    &mut self,
+    event: ClientEvent,
+  ) {
+    self.latest += 1;
+    self.recent.push_back((self.latest, event));
+    while self.recent.len() > RECENT_UPDATES {
+      self.recent.pop_front();
+    }
+  }
+
+  /// The number of the latest build's changes
+  pub fn latest(&self) -> u64 {
+    self.latest
+  }
+
+  /// The changes after `since` combined. Pages that
+  /// missed some (or are from before ssb
+  /// restarted) reload.
+  pub fn since(
+    &self,
+    since: u64,
+  ) -> ClientEvent {
+    let missed = since > self.latest
+      || (since < self.latest
+        && self
+          .recent
+          .front()
+          .is_none_or(|(id, _)| *id > since + 1));
+    if missed {
+      return ClientEvent::Reload;
+    }
+    let events = self
+      .recent
+      .iter()
+      .filter(|(id, _)| *id > since)
+      .map(|(_, event)| event)
+      .collect::<Vec<_>>();
+    if events.contains(&&ClientEvent::Reload) {
+      return ClientEvent::Reload;
+    }
+    let (stylesheets, pages) = events
+      .into_iter()
+      .filter_map(|event| match event {
+        ClientEvent::Update { stylesheets, pages } => {
+          Some((stylesheets, pages))
+        }
+        ClientEvent::Reload => None,
+      })
+      .fold(
+        (vec![], vec![]),
+        |(mut all_stylesheets, mut all_pages),
+         (stylesheets, pages)| {
+          all_stylesheets.extend(stylesheets.clone());
+          all_pages.extend(pages.clone());
+          (all_stylesheets, all_pages)
+        },
+      );
+    ClientEvent::Update {
+      stylesheets: stylesheets
+        .into_iter()
+        .unique()
+        .collect(),
+      pages: pages.into_iter().unique().collect(),
+    }
+  }
+}
+
+pub type ClientEvents = Arc<Mutex<ClientUpdates>>;
+
+pub fn client_events() -> ClientEvents {
+  Arc::new(Mutex::new(ClientUpdates::default()))
+}
+
+#[derive(Clone, Debug)]
+pub struct ClientState {
+  pub sync: bool,
+  pub events: ClientEvents,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdatesQuery {
+  since: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Updates {
+  id: u64,
+  #[serde(flatten)]
+  event: ClientEvent,
+}
+
+const CLIENT_SCRIPT: &str = include_str!("client.js");
+
+/// The script pages load and where it gets the
+/// changes from.
+pub fn client_routes(events: ClientEvents) -> Router {
+  let path = |name: &str| {
+    format!("{}/{}", INTROSPECTION_PREFIX, name)
+  };
+  Router::new()
+    .route(&path("client.js"), get(client_script))
+    .route(&path("updates"), get(updates))
+    .with_state(events)
+}
+
+async fn client_script() -> Response {
+  (
+    [
+      (CONTENT_TYPE, "text/javascript"),
+      (CACHE_CONTROL, "no-cache"),
+    ],
+    CLIENT_SCRIPT,
+  )
+    .into_response()
+}
+
+async fn updates(
+  State(events): State<ClientEvents>,
+  Query(query): Query<UpdatesQuery>,
+) -> Response {
+  let events = events.lock().unwrap();
+  (
+    [(CACHE_CONTROL, "no-store")],
+    Json(Updates {
+      id: events.latest(),
+      event: events.since(query.since),
+    }),
+  )
+    .into_response()
+}
+
+/// Adds the client scripts to pages. Encoded
+/// responses are passed through untouched.
+pub async fn inject_client(
+  State(state): State<ClientState>,
+  request: Request,
+  next: Next,
+) -> Response {
+  let response = next.run(request).await;
+  let is_page = response
+    .headers()
+    .get(CONTENT_TYPE)
+    .and_then(|value| value.to_str().ok())
+    .is_some_and(|value| value.starts_with("text/html"))
+    && !response.headers().contains_key(CONTENT_ENCODING);
+  if !is_page {
+    return response;
+  }
+  let (mut parts, body) = response.into_parts();
+  let Ok(html) = to_bytes(body, usize::MAX).await else {
+    return StatusCode::INTERNAL_SERVER_ERROR
+      .into_response();
+  };
+  parts.headers.remove(CONTENT_LENGTH);
+  let latest = state.events.lock().unwrap().latest();
+  Response::from_parts(
+    parts,
+    Body::from(add_client_scripts(
+      &html,
+      &client_scripts(state.sync, latest),
+    )),
+  )
+}
+
+/// The script tags for the client and, when it's
+/// on, sync. The client is told the latest
+/// changes the page already has.
+pub fn client_scripts(
+  sync: bool,
+  latest: u64,
+) -> String {
+  let client = format!(
+    r#"<script src="{}/client.js" data-live-reload="{}" data-update="{}" defer></script>"#,
+    INTROSPECTION_PREFIX, LIVE_RELOAD_PREFIX, latest
+  );
+  if sync {
+    client
+      + &format!(
+        r#"<script src="{}/sync.js" defer></script>"#,
+        INTROSPECTION_PREFIX
+      )
+  } else {
+    client
+  }
+}
+
+/// Puts the scripts before the last `</body>` or
+/// at the end if there isn't one.
+pub fn add_client_scripts(
+  html: &[u8],
+  scripts: &str,
+) -> Vec<u8> {
+  let at = html
+    .to_ascii_lowercase()
+    .windows(7)
+    .rposition(|window| window == b"</body>")
+    .unwrap_or(html.len());
+  [&html[..at], scripts.as_bytes(), &html[at..]].concat()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case(
+    "<body><p>alfa</p></BODY></html>",
+    r#"<body><p>alfa</p><script src="/__ssb/client.js" data-live-reload="/tower-livereload" data-update="3" defer></script></BODY></html>"#
+  )]
+  #[case(
+    "<p>alfa</p>",
+    r#"<p>alfa</p><script src="/__ssb/client.js" data-live-reload="/tower-livereload" data-update="3" defer></script>"#
+  )]
+  fn add_client_scripts_test(
+    #[case] html: &str,
+    #[case] expected: &str,
+  ) {
+    assert_eq!(
+      expected,
+      String::from_utf8(add_client_scripts(
+        html.as_bytes(),
+        &client_scripts(false, 3)
+      ))
+      .unwrap()
+    );
+  }
+
+  #[test]
+  fn client_scripts_sync_test() {
+    assert_eq!(
+      concat!(
+        r#"<script src="/__ssb/client.js" data-live-reload="/tower-livereload" data-update="0" defer></script>"#,
+        r#"<script src="/__ssb/sync.js" defer></script>"#,
+      ),
+      client_scripts(true, 0)
+    );
+  }
+
+  #[test]
+  fn updates_json_test() {
+    assert_eq!(
+      r#"{"id":2,"type":"update","stylesheets":["/main.css"],"pages":[]}"#,
+      serde_json::to_string(&Updates {
+        id: 2,
+        event: ClientEvent::Update {
+          stylesheets: vec!["/main.css".to_string()],
+          pages: vec![],
+        },
+      })
+      .unwrap()
+    );
+  }
+
+  fn update(
+    stylesheets: &[&str],
+    pages: &[&str],
+  ) -> ClientEvent {
+    ClientEvent::Update {
+      stylesheets: stylesheets
+        .iter()
+        .map(|path| path.to_string())
+        .collect(),
+      pages: pages
+        .iter()
+        .map(|path| path.to_string())
+        .collect(),
+    }
+  }
+
+  #[rstest]
+  #[case(3, update(&[], &[]))]
+  #[case(2, update(&["/b.css", "/a.css"], &[]))]
+  #[case(1, update(&["/a.css", "/b.css"], &["/about/index.html"]))]
+  #[case(0, ClientEvent::Reload)]
+  #[case(4, ClientEvent::Reload)]
+  fn since_test(
+    #[case] since: u64,
+    #[case] expected: ClientEvent,
+  ) {
+    let mut updates = ClientUpdates::default();
+    updates.push(ClientEvent::Reload);
+    updates
+      .push(update(&["/a.css"], &["/about/index.html"]));
+    updates.push(update(&["/b.css", "/a.css"], &[]));
+    assert_eq!(expected, updates.since(since));
+  }
+
+  #[test]
+  fn since_missed_test() {
+    let mut updates = ClientUpdates::default();
+    (0..RECENT_UPDATES + 2).for_each(|_| {
+      updates.push(update(&["/a.css"], &[]))
+    });
+    assert_eq!(ClientEvent::Reload, updates.since(1));
+    assert_eq!(
+      update(&["/a.css"], &[]),
+      updates.since(3)
+    );
+  }
+}
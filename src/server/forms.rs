@@ -0,0 +1,535 @@
+use crate::builder::utils::mj_slugify;
+use crate::config::Config;
+use crate::server::base_path::strip_base_path;
+use crate::server::netlify_rules::file_exists;
+use crate::status::SharedStatus;
+use anyhow::{Result, anyhow};
+use axum::Router;
+use axum::extract::{
+  DefaultBodyLimit, Form, FromRequest, Multipart,
+  Request, State,
+};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{Method, StatusCode};
+use axum::middleware::{Next, from_fn_with_state};
+use axum::response::{
+  Html, IntoResponse, Redirect, Response,
+};
+use regex::Regex;
+use serde_json::{Map, Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+/// A `<form>` from the site with a `netlify` or
+/// `data-netlify` attribute. Forms without an
+/// `action` post back to their own page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetlifyForm {
+  pub name: Option<String>,
+  pub action: String,
+  pub has_action: bool,
+}
+
+/// The most a form submission (uploads included)
+/// can be. Bigger ones get a 413.
+pub const FORM_BODY_LIMIT: usize = 32 * 1024 * 1024;
+
+#[derive(Clone, Debug)]
+pub struct FormsState {
+  pub config: Config,
+  pub status: SharedStatus,
+}
+
+/// Adds form capturing to a router with a body
+/// limit that fits file uploads.
+pub fn capture_forms_layer(
+  router: Router,
+  state: FormsState,
+) -> Router {
+  router
+    .layer(from_fn_with_state(state, capture_forms))
+    .layer(DefaultBodyLimit::max(FORM_BODY_LIMIT))
+}
+
+/// Saves `POST`s to configured form paths or to
+/// the site's Netlify forms as JSON in the logs
+/// folder then redirects or shows a thank you page.
+/// Other requests pass through.
+pub async fn capture_forms(
+  State(state): State<FormsState>,
+  request: Request,
+  next: Next,
+) -> Response {
+  if request.method() != Method::POST {
+    return next.run(request).await;
+  }
+  let config = &state.config;
+  let path = request.uri().path().to_string();
+  let configured = config
+    .form_paths
+    .iter()
+    .any(|form_path| same_path(form_path, &path));
+  let netlify_form = state
+    .status
+    .lock()
+    .unwrap()
+    .forms
+    .iter()
+    .find(|form| same_path(&form.action, &path))
+    .cloned();
+  if !configured && netlify_form.is_none() {
+    return next.run(request).await;
+  }
+  let received = chrono::Local::now();
+  let slug = mj_slugify(path.clone());
+  let base_name = format!(
+    "{}-{}",
+    received.format("%Y-%m-%d_%H-%M-%S%.3f"),
+    if slug.is_empty() { "index" } else { &slug }
+  );
+  let (fields, files) = match read_submission(
+    request,
+    &config.forms_logs(),
+    &base_name,
+  )
+  .await
+  {
+    Ok(submission) => submission,
+    Err(e) => {
+      warn!(
+        "Could not read form submission to {}: {}",
+        path, e
+      );
+      return (StatusCode::BAD_REQUEST, e.to_string())
+        .into_response();
+    }
+  };
+  let name = fields
+    .get("form-name")
+    .and_then(|name| name.as_str())
+    .map(|name| name.to_string())
+    .or(
+      netlify_form
+        .as_ref()
+        .and_then(|form| form.name.clone()),
+    );
+  let submission = json!({
+    "form": name,
+    "path": path,
+    "received": received.to_rfc3339(),
+    "fields": fields,
+    "files": files,
+  });
+  let output_path = config
+    .forms_logs()
+    .join(format!("{}.json", base_name));
+  let saved = fs::create_dir_all(config.forms_logs())
+    .and_then(|_| {
+      fs::write(
+        &output_path,
+        serde_json::to_string_pretty(&submission)
+          .unwrap_or_default(),
+      )
+    });
+  match saved {
+    Ok(_) => info!(
+      "Saved form submission to {}",
+      output_path.display()
+    ),
+    Err(e) => {
+      warn!(
+        "Could not write {}: {}",
+        output_path.display(),
+        e
+      );
+      return StatusCode::INTERNAL_SERVER_ERROR
+        .into_response();
+    }
+  }
+  let redirect = config.form_redirect.clone().or(
+    netlify_form
+      .filter(|form| form.has_action)
+      .map(|form| form.action)
+      .filter(|action| {
+        file_exists(&config.output_root, action)
+      }),
+  );
+  match redirect {
+    Some(redirect) => {
+      Redirect::to(&redirect).into_response()
+    }
+    None => Html(THANK_YOU_PAGE).into_response(),
+  }
+}
+
+// Text fields go in `fields`. Uploaded files are
+// saved in a folder next to the JSON and listed in
+// `files`. Fields with the same name are collected
+// into a list.
+async fn read_submission(
+  request: Request,
+  forms_dir: &Path,
+  base_name: &str,
+) -> Result<(Map<String, Value>, Vec<Value>)> {
+  let content_type = request
+    .headers()
+    .get(CONTENT_TYPE)
+    .and_then(|value| value.to_str().ok())
+    .unwrap_or("")
+    .to_string();
+  let mut fields = Map::new();
+  let mut files = vec![];
+  if content_type.starts_with("multipart/form-data") {
+    let mut multipart =
+      Multipart::from_request(request, &()).await?;
+    while let Some(field) = multipart.next_field().await?
+    {
+      let name = field.name().unwrap_or("").to_string();
+      match field
+        .file_name()
+        .map(|file_name| file_name.to_string())
+      {
+        Some(file_name) => {
+          let field_type =
+            field.content_type().map(|t| t.to_string());
+          let bytes = field.bytes().await?;
+          // Only the last part of the name is used so
+          // uploads can't be written outside the folder.
+          let saved_name = Path::new(&file_name)
+            .file_name()
+            .map(PathBuf::from)
+            .unwrap_or(PathBuf::from("upload"));
+          let saved_path = forms_dir
+            .join(format!("{}-files", base_name))
+            .join(format!(
+              "{}-{}",
+              files.len() + 1,
+              saved_name.display()
+            ));
+          let saved_to = if bytes.is_empty() {
+            None
+          } else {
+            fs::create_dir_all(
+              saved_path.parent().unwrap(),
+            )?;
+            fs::write(&saved_path, &bytes)?;
+            Some(saved_path.display().to_string())
+          };
+          files.push(json!({
+            "field": name,
+            "file_name": file_name,
+            "content_type": field_type,
+            "size": bytes.len(),
+            "saved_to": saved_to,
+          }));
+        }
+        None => add_field(
+          &mut fields,
+          name,
+          Value::from(field.text().await?),
+        ),
+      }
+    }
+  } else if content_type.starts_with("application/json") {
+    let body = axum::body::to_bytes(
+      request.into_body(),
+      FORM_BODY_LIMIT,
+    )
+    .await?;
+    match serde_json::from_slice::<Value>(&body)? {
+      Value::Object(object) => fields = object,
+      value => {
+        add_field(&mut fields, "body".to_string(), value)
+      }
+    }
+  } else if content_type
+    .starts_with("application/x-www-form-urlencoded")
+  {
+    let Form(pairs) =
+      Form::<Vec<(String, String)>>::from_request(
+        request,
+        &(),
+      )
+      .await
+      .map_err(|e| anyhow!(e.body_text()))?;
+    for (name, value) in pairs {
+      add_field(&mut fields, name, Value::from(value));
+    }
+  } else {
+    let body = axum::body::to_bytes(
+      request.into_body(),
+      FORM_BODY_LIMIT,
+    )
+    .await?;
+    add_field(
+      &mut fields,
+      "body".to_string(),
+      Value::from(
+        String::from_utf8_lossy(&body).to_string(),
+      ),
+    );
+  }
+  Ok((fields, files))
+}
+
+fn add_field(
+  fields: &mut Map<String, Value>,
+  name: String,
+  value: Value,
+) {
+  match fields.get_mut(&name) {
+    Some(Value::Array(values)) => values.push(value),
+    Some(existing) => {
+      *existing =
+        Value::from(vec![existing.clone(), value])
+    }
+    None => {
+      fields.insert(name, value);
+    }
+  }
+}
+
+/// Finds the Netlify forms in the built site.
+/// The builder does this after each build.
+pub fn site_forms(
+  output_root: &Path,
+  base_path: &str,
+) -> Vec<NetlifyForm> {
+  WalkDir::new(output_root)
+    .into_iter()
+    .filter_map(|entry| entry.ok())
+    .filter(|entry| {
+      entry
+        .path()
+        .extension()
+        .is_some_and(|ext| ext == "html")
+    })
+    .flat_map(|entry| {
+      let page_url = entry
+        .path()
+        .strip_prefix(output_root)
+        .map(page_url)
+        .unwrap_or_default();
+      fs::read_to_string(entry.path())
+        .map(|html| {
+          netlify_forms(&html, &page_url, base_path)
+        })
+        .unwrap_or_default()
+    })
+    .collect()
+}
+
+/// Finds the Netlify forms in a page. The base
+/// path is taken off actions so they match the
+/// paths requests have once it's been stripped.
+pub fn netlify_forms(
+  html: &str,
+  page_url: &str,
+  base_path: &str,
+) -> Vec<NetlifyForm> {
+  let form_tag =
+    Regex::new(r"(?is)<form\b([^>]*)>").unwrap();
+  let attribute = Regex::new(
+    r#"([\w:-]+)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+)))?"#,
+  )
+  .unwrap();
+  form_tag
+    .captures_iter(html)
+    .filter_map(|form_tag| {
+      let mut is_netlify = false;
+      let mut name = None;
+      let mut action = None;
+      for attribute in
+        attribute.captures_iter(&form_tag[1])
+      {
+        let value = attribute
+          .get(2)
+          .or(attribute.get(3))
+          .or(attribute.get(4))
+          .map(|value| value.as_str().to_string());
+        match attribute[1].to_lowercase().as_str() {
+          "netlify" | "data-netlify" => {
+            is_netlify = value.as_deref() != Some("false")
+          }
+          "name" => name = value,
+          "action" => {
+            action = value
+              .filter(|a| !a.is_empty())
+              .map(|a| without_base_path(base_path, a))
+          }
+          _ => {}
+        }
+      }
+      is_netlify.then(|| NetlifyForm {
+        name,
+        has_action: action.is_some(),
+        action: action.unwrap_or(page_url.to_string()),
+      })
+    })
+    .collect()
+}
+
+// The URL a page in the output folder is served at.
+fn page_url(relative_path: &Path) -> String {
+  let path = relative_path.display().to_string();
+  match path.strip_suffix("index.html") {
+    Some(folder) => format!("/{}", folder),
+    None => format!("/{}", path),
+  }
+}
+
+fn without_base_path(
+  base_path: &str,
+  url: String,
+) -> String {
+  if base_path.is_empty() {
+    return url;
+  }
+  url
+    .parse()
+    .ok()
+    .and_then(|uri| strip_base_path(base_path, &uri))
+    .map(|uri| uri.to_string())
+    .unwrap_or(url)
+}
+
+fn same_path(
+  a: &str,
+  b: &str,
+) -> bool {
+  a.trim_end_matches('/') == b.trim_end_matches('/')
+}
+
+const THANK_YOU_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head><style>body { background: black; color: white;}</style></head>
+<body>
+<p>Thank you! Your form submission has been received.</p>
+<p><a href="javascript:history.back()">Go back</a></p>
+</body>
+</html>"#;
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+  use tower::ServiceExt as _;
+
+  #[rstest]
+  #[case(
+    r#"<form name="contact" method="POST" data-netlify="true">"#,
+    Some(NetlifyForm {
+      name: Some("contact".to_string()),
+      action: "/about/".to_string(),
+      has_action: false,
+    })
+  )]
+  #[case(
+    r#"<form netlify action="/thanks/" name='signup'>"#,
+    Some(NetlifyForm {
+      name: Some("signup".to_string()),
+      action: "/thanks/".to_string(),
+      has_action: true,
+    })
+  )]
+  #[case(
+    r#"<form name="search" action="/search">"#,
+    None
+  )]
+  #[case(r#"<form data-netlify="false">"#, None)]
+  fn netlify_forms_test(
+    #[case] html: &str,
+    #[case] expected: Option<NetlifyForm>,
+  ) {
+    assert_eq!(
+      expected,
+      netlify_forms(html, "/about/", "")
+        .into_iter()
+        .next()
+    );
+  }
+
+  #[rstest]
+  #[case("/project/thanks/", "/thanks/")]
+  #[case("/project/thanks/?a=1", "/thanks/?a=1")]
+  #[case("/thanks/", "/thanks/")]
+  #[case("https://example.com/", "https://example.com/")]
+  fn netlify_forms_base_path_test(
+    #[case] action: &str,
+    #[case] expected: &str,
+  ) {
+    let html =
+      format!(r#"<form netlify action="{}">"#, action);
+    assert_eq!(
+      vec![NetlifyForm {
+        name: None,
+        action: expected.to_string(),
+        has_action: true,
+      }],
+      netlify_forms(&html, "/about/", "/project")
+    );
+  }
+
+  #[rstest]
+  #[case("index.html", "/")]
+  #[case("about/index.html", "/about/")]
+  #[case("404.html", "/404.html")]
+  fn page_url_test(
+    #[case] path: &str,
+    #[case] expected: &str,
+  ) {
+    assert_eq!(expected, page_url(Path::new(path)));
+  }
+
+  #[tokio::test]
+  async fn large_upload_test() {
+    let logs_root = std::env::temp_dir()
+      .join(format!("ssb-forms-{}", std::process::id()));
+    let config = Config {
+      logs_root: logs_root.clone(),
+      form_paths: vec!["/contact".to_string()],
+      ..Config::default()
+    };
+    let app = capture_forms_layer(
+      Router::new(),
+      FormsState {
+        config,
+        status: crate::status::BuildStatus::shared(),
+      },
+    );
+    let body = format!(
+      "--x\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\r\n{}\r\n--x--\r\n",
+      "a".repeat(3 * 1024 * 1024)
+    );
+    let response = app
+      .oneshot(
+        Request::post("/contact")
+          .header(
+            CONTENT_TYPE,
+            "multipart/form-data; boundary=x",
+          )
+          .body(axum::body::Body::from(body))
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    let _ = fs::remove_dir_all(&logs_root);
+    assert_eq!(StatusCode::OK, response.status());
+  }
+
+  #[test]
+  fn add_field_test() {
+    let mut fields = Map::new();
+    add_field(&mut fields, "a".to_string(), json!("1"));
+    add_field(&mut fields, "b".to_string(), json!("2"));
+    add_field(&mut fields, "b".to_string(), json!("3"));
+    add_field(&mut fields, "b".to_string(), json!("4"));
+    assert_eq!(
+      json!({"a": "1", "b": ["2", "3", "4"]}),
+      Value::Object(fields)
+    );
+  }
+}
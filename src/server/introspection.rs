@@ -0,0 +1,305 @@
+use crate::builder::utils::LazyFiles;
+use crate::status::SharedStatus;
+use axum::Router;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use minijinja::Value;
+use minijinja::value::Object;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// Requests under this prefix are answered by the
+/// preview server itself instead of the site.
+pub const INTROSPECTION_PREFIX: &str = "/__ssb";
+
+/// `load` maps the name of a lazily loaded value
+/// (e.g. `data`) to the comma separated entries to
+/// load from it.
+#[derive(Debug, Deserialize)]
+pub struct ContextQuery {
+  page: Option<String>,
+  #[serde(flatten)]
+  load: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogsQuery {
+  limit: Option<usize>,
+  level: Option<String>,
+}
+
+/// Endpoints for checking on the builder:
+/// - `/__ssb/status` build state, duration and errors
+/// - `/__ssb/routes` what each content file becomes
+/// - `/__ssb/context?page=/about/` a page's template
+///   context. Data, markdown and highlighted files
+///   are listed and only loaded when asked for with
+///   e.g. `&data=a.csv,b.json`
+/// - `/__ssb/logs?limit=50&level=warn` recent log
+///   lines
+/// - `/__ssb/not-found` paths that returned 404
+pub fn introspection_routes(
+  status: SharedStatus
+) -> Router {
+  let path = |name: &str| {
+    format!("{}/{}", INTROSPECTION_PREFIX, name)
+  };
+  Router::new()
+    .route(INTROSPECTION_PREFIX, get(endpoints))
+    .route(&path(""), get(endpoints))
+    .route(&path("status"), get(build_status))
+    .route(&path("routes"), get(routes))
+    .route(&path("context"), get(page_context))
+    .route(&path("logs"), get(recent_logs))
+    .route(&path("not-found"), get(not_found))
+    .with_state(status)
+}
+
+async fn endpoints() -> Response {
+  let path = |name: &str| {
+    format!("{}/{}", INTROSPECTION_PREFIX, name)
+  };
+  pretty_json(
+    StatusCode::OK,
+    json!({
+      "status": path("status"),
+      "routes": path("routes"),
+      "context": path("context?page=/&data=a.csv"),
+      "logs": path("logs?limit=100"),
+      "not_found": path("not-found"),
+    }),
+  )
+}
+
+async fn build_status(
+  State(status): State<SharedStatus>
+) -> Response {
+  let status = status.lock().unwrap();
+  let started =
+    status.started.map(|time| time.to_rfc3339());
+  let finished =
+    status.finished.map(|time| time.to_rfc3339());
+  pretty_json(
+    StatusCode::OK,
+    json!({
+      "state": status.state(),
+      "build_count": status.build_count,
+      "started": started,
+      "finished": finished,
+      "duration_ms": status.duration_ms(),
+      "errors": status.errors,
+      "changed_outputs": status.changed_outputs,
+    }),
+  )
+}
+
+async fn routes(
+  State(status): State<SharedStatus>
+) -> Response {
+  pretty_json(
+    StatusCode::OK,
+    &status.lock().unwrap().routes,
+  )
+}
+
+// The status lock is let go before the context is
+// serialized since loading files can log.
+async fn page_context(
+  State(status): State<SharedStatus>,
+  Query(query): Query<ContextQuery>,
+) -> Response {
+  let (found, pages) = {
+    let status = status.lock().unwrap();
+    let found = query
+      .page
+      .as_deref()
+      .and_then(|page| status.page_key(page))
+      .and_then(|key| {
+        Some((
+          status.pages.get(&key)?.clone(),
+          status.site_context.clone()?,
+        ))
+      });
+    let pages = status
+      .routes
+      .iter()
+      .filter(|route| {
+        status.pages.contains_key(&route.source)
+      })
+      .filter_map(|route| route.url.clone())
+      .collect::<Vec<_>>();
+    (found, pages)
+  };
+  match found {
+    Some((file, site_context)) => {
+      match context_json(
+        &file,
+        &site_context,
+        &query.load,
+      ) {
+        Ok(value) => pretty_json(StatusCode::OK, value),
+        Err(e) => pretty_json(
+          StatusCode::INTERNAL_SERVER_ERROR,
+          json!({ "error": e.to_string() }),
+        ),
+      }
+    }
+    None => pretty_json(
+      StatusCode::NOT_FOUND,
+      json!({
+        "error": "Use ?page= with a page's URL or source path",
+        "pages": pages,
+      }),
+    ),
+  }
+}
+
+async fn recent_logs(
+  State(status): State<SharedStatus>,
+  Query(query): Query<LogsQuery>,
+) -> Response {
+  let status = status.lock().unwrap();
+  let lines = status
+    .recent_logs
+    .iter()
+    .filter(|line| {
+      query.level.as_ref().is_none_or(|level| {
+        line.level.eq_ignore_ascii_case(level)
+      })
+    })
+    .collect::<Vec<_>>();
+  let limit =
+    query.limit.unwrap_or(lines.len()).min(lines.len());
+  pretty_json(
+    StatusCode::OK,
+    &lines[lines.len() - limit..],
+  )
+}
+
+async fn not_found(
+  State(status): State<SharedStatus>
+) -> Response {
+  pretty_json(
+    StatusCode::OK,
+    &status.lock().unwrap().not_found,
+  )
+}
+
+/// The page's context as JSON. Lazily loaded maps
+/// become their keys and just the entries in
+/// `load`, so looking at a page doesn't load every
+/// file in the site.
+fn context_json(
+  file: &Value,
+  site_context: &Value,
+  load: &BTreeMap<String, String>,
+) -> Result<serde_json::Value, serde_json::Error> {
+  let mut context = serde_json::Map::new();
+  context.insert(
+    "file".to_string(),
+    serde_json::to_value(file)?,
+  );
+  for name in
+    site_context.try_iter().into_iter().flatten()
+  {
+    let Ok(value) = site_context.get_item(&name) else {
+      continue;
+    };
+    let value = match value.downcast_object::<LazyFiles>()
+    {
+      Some(files) => {
+        let wanted = load
+          .get(&name.to_string())
+          .map(|keys| keys.split(',').collect::<Vec<_>>())
+          .unwrap_or_default();
+        let loaded = wanted
+          .into_iter()
+          .filter_map(|key| {
+            let value =
+              files.get_value(&Value::from(key))?;
+            Some(
+              serde_json::to_value(value)
+                .map(|value| (key.to_string(), value)),
+            )
+          })
+          .collect::<Result<serde_json::Map<_, _>, _>>(
+          )?;
+        json!({ "keys": files.keys(), "loaded": loaded })
+      }
+      None => serde_json::to_value(value)?,
+    };
+    context.insert(name.to_string(), value);
+  }
+  Ok(serde_json::Value::Object(context))
+}
+
+fn pretty_json(
+  status: StatusCode,
+  value: impl Serialize,
+) -> Response {
+  (
+    status,
+    [(CONTENT_TYPE, "application/json")],
+    serde_json::to_string_pretty(&value)
+      .unwrap_or_default(),
+  )
+    .into_response()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use minijinja::context;
+  use pretty_assertions::assert_eq;
+  use std::collections::BTreeSet;
+  use std::path::Path;
+  use std::sync::Arc;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  #[test]
+  fn context_json_test() {
+    let loads = Arc::new(AtomicUsize::new(0));
+    let counter = loads.clone();
+    let data = Value::from_object(LazyFiles::new(
+      Path::new("content"),
+      BTreeSet::from([
+        "a.csv".to_string(),
+        "b.json".to_string(),
+      ]),
+      move |_| {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Some(Value::from(1))
+      },
+    ));
+    let site_context = context!(
+      data => data,
+      folders => vec!["about"],
+    );
+    let load = BTreeMap::from([(
+      "data".to_string(),
+      "b.json,missing.json".to_string(),
+    )]);
+    let got = context_json(
+      &Value::from("about/index.html"),
+      &site_context,
+      &load,
+    )
+    .unwrap();
+    assert_eq!(
+      json!({
+        "file": "about/index.html",
+        "data": {
+          "keys": ["a.csv", "b.json"],
+          "loaded": { "b.json": 1 },
+        },
+        "folders": ["about"],
+      }),
+      got
+    );
+    assert_eq!(1, loads.load(Ordering::Relaxed));
+  }
+}
@@ -0,0 +1,179 @@
+use minijinja::{Environment, context};
+use percent_encoding::{
+  AsciiSet, NON_ALPHANUMERIC, percent_decode_str,
+  utf8_percent_encode,
+};
+use serde::Serialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+// What's escaped in a file name to make a link.
+const HREF_ESCAPES: &AsciiSet = &NON_ALPHANUMERIC
+  .remove(b'-')
+  .remove(b'.')
+  .remove(b'_')
+  .remove(b'~');
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ListingEntry {
+  pub name: String,
+  pub href: String,
+  pub is_dir: bool,
+  pub size: Option<String>,
+}
+
+/// Finds the folder in the output folder for a
+/// request path. Paths that leave the output folder
+/// or aren't folders get `None`.
+pub fn listing_dir(
+  output_root: &Path,
+  request_path: &str,
+) -> Option<PathBuf> {
+  let decoded = percent_decode_str(request_path)
+    .decode_utf8()
+    .ok()?;
+  let relative =
+    Path::new(decoded.trim_start_matches('/'));
+  if relative.components().any(|component| {
+    !matches!(component, Component::Normal(_))
+  }) {
+    return None;
+  }
+  let dir = output_root.join(relative);
+  dir.is_dir().then_some(dir)
+}
+
+/// Folders first then files, each sorted by name.
+/// Hidden files are left out.
+pub fn listing_entries(dir: &Path) -> Vec<ListingEntry> {
+  let mut entries = fs::read_dir(dir)
+    .map(|entries| {
+      entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+          let name =
+            entry.file_name().to_str()?.to_string();
+          if name.starts_with('.') {
+            return None;
+          }
+          let metadata = entry.metadata().ok()?;
+          Some(ListingEntry {
+            href: utf8_percent_encode(
+              &name,
+              HREF_ESCAPES,
+            )
+            .to_string(),
+            name,
+            is_dir: metadata.is_dir(),
+            size: metadata
+              .is_file()
+              .then(|| format_size(metadata.len())),
+          })
+        })
+        .collect::<Vec<_>>()
+    })
+    .unwrap_or_default();
+  entries.sort_by(|a, b| {
+    b.is_dir.cmp(&a.is_dir).then(a.name.cmp(&b.name))
+  });
+  entries
+}
+
+/// Renders the listing page for a folder.
+pub fn directory_listing(
+  request_path: &str,
+  dir: &Path,
+) -> Result<String, minijinja::Error> {
+  let mut env = Environment::new();
+  env.add_template("listing.html", LISTING_TEMPLATE)?;
+  env.get_template("listing.html")?.render(context!(
+    path => percent_decode_str(request_path)
+      .decode_utf8_lossy()
+      .to_string(),
+    is_root => request_path == "/",
+    entries => listing_entries(dir),
+  ))
+}
+
+pub fn format_size(bytes: u64) -> String {
+  match bytes {
+    0..1024 => format!("{} B", bytes),
+    1024..1_048_576 => {
+      format!("{:.1} KB", bytes as f64 / 1024.0)
+    }
+    _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+  }
+}
+
+const LISTING_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="robots" content="noindex">
+<title>Index of {{ path }}</title>
+<style>
+body { background: black; color: white; font-family: system-ui, sans-serif; margin: 2rem auto; max-width: 48rem; padding: 0 1rem; }
+a { color: #8cf; text-decoration: none; }
+a:hover { text-decoration: underline; }
+.dev-only { border: 1px dashed #fc6; color: #fc6; padding: 0.5rem 0.75rem; }
+table { border-collapse: collapse; width: 100%; }
+td { border-bottom: 1px solid #333; padding: 0.4rem 0.5rem; }
+td.size { color: #aaa; text-align: right; white-space: nowrap; }
+</style>
+</head>
+<body>
+<p class="dev-only">Directory listing from the preview server. This page is not part of the built site.</p>
+<h1>Index of {{ path }}</h1>
+<table>
+{% if not is_root %}<tr><td><a href="../">../</a></td><td class="size"></td></tr>
+{% endif %}{% for entry in entries %}<tr><td><a href="{{ entry.href }}{% if entry.is_dir %}/{% endif %}">{{ entry.name }}{% if entry.is_dir %}/{% endif %}</a></td><td class="size">{{ entry.size or "" }}</td></tr>
+{% endfor %}</table>
+</body>
+</html>"#;
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case(0, "0 B")]
+  #[case(1023, "1023 B")]
+  #[case(1536, "1.5 KB")]
+  #[case(3_145_728, "3.0 MB")]
+  fn format_size_test(
+    #[case] bytes: u64,
+    #[case] expected: &str,
+  ) {
+    assert_eq!(expected, format_size(bytes));
+  }
+
+  #[rstest]
+  #[case("/src/", true)]
+  #[case("/src/server/", true)]
+  #[case("/src/lib.rs", false)]
+  #[case("/src/../Cargo.toml", false)]
+  #[case("/src/%2e%2e/", false)]
+  #[case("/missing/", false)]
+  fn listing_dir_test(
+    #[case] request_path: &str,
+    #[case] expected: bool,
+  ) {
+    assert_eq!(
+      expected,
+      listing_dir(Path::new("."), request_path).is_some()
+    );
+  }
+
+  #[test]
+  fn directory_listing_test() {
+    let html =
+      directory_listing("/src/", Path::new("src"))
+        .unwrap();
+    assert!(html.contains(r#"<p class="dev-only">"#));
+    assert!(html.contains(r#"<a href="../">"#));
+    assert!(html.contains(r#"<a href="server/">"#));
+    assert!(html.contains(r#"<a href="lib.rs">"#));
+  }
+}
@@ -0,0 +1,217 @@
+use crate::config::Config;
+use crate::status::SharedStatus;
+use axum::body::to_bytes;
+use axum::extract::{Query, Request, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use minijinja::{Value, context};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tracing::{error, warn};
+
+#[derive(Clone, Debug)]
+pub struct MockApiState {
+  pub config: Config,
+  pub status: SharedStatus,
+}
+
+/// Answers requests under the API prefix with the
+/// matching file from the API folder. Files are
+/// rendered as templates with `params`, `query`
+/// and `body` available. Requests that don't match
+/// a route fall through to the site. The routes
+/// and environment come from the last build.
+pub async fn serve_mock_api(
+  State(state): State<MockApiState>,
+  request: Request,
+  next: Next,
+) -> Response {
+  let config = &state.config;
+  let path = request.uri().path().to_string();
+  if !path.starts_with(&config.api_prefix) {
+    return next.run(request).await;
+  }
+  let found = {
+    let status = state.status.lock().unwrap();
+    status
+      .mock_api
+      .find(request.method().as_str(), &path)
+      .map(|(route, params)| (route.clone(), params))
+      .zip(status.mock_env.clone())
+  };
+  let Some(((route, params), env)) = found else {
+    return next.run(request).await;
+  };
+  let query =
+    Query::<BTreeMap<String, String>>::try_from_uri(
+      request.uri(),
+    )
+    .map(|Query(query)| query)
+    .unwrap_or_default();
+  let body =
+    match to_bytes(request.into_body(), 1 << 20).await {
+      Ok(bytes) => {
+        let text =
+          String::from_utf8_lossy(&bytes).to_string();
+        serde_json::from_str::<Value>(&text)
+          .unwrap_or(Value::from(text))
+      }
+      Err(_) => Value::from(()),
+    };
+  if let Some(latency) = route.settings.latency {
+    tokio::time::sleep(Duration::from_millis(latency))
+      .await;
+  }
+  let rendered =
+    match std::fs::read_to_string(&route.file) {
+      Ok(template) => env
+        .render_str(
+          &template,
+          context!(
+            params => params,
+            query => query,
+            body => body,
+          ),
+        )
+        .map_err(|e| format!("{:#}", e)),
+      Err(e) => Err(e.to_string()),
+    };
+  let mut response = match rendered {
+    Ok(content) => {
+      let content_type =
+        mime_guess::from_path(&route.file)
+          .first_or_octet_stream()
+          .to_string();
+      let status = route
+        .settings
+        .status
+        .and_then(|status| {
+          StatusCode::from_u16(status).ok()
+        })
+        .unwrap_or(StatusCode::OK);
+      (status, [(CONTENT_TYPE, content_type)], content)
+        .into_response()
+    }
+    Err(e) => {
+      error!("{}: {}", route.file.display(), e);
+      (StatusCode::INTERNAL_SERVER_ERROR, e)
+        .into_response()
+    }
+  };
+  for (name, value) in &route.settings.headers {
+    match (
+      HeaderName::from_bytes(name.as_bytes()),
+      HeaderValue::from_str(value),
+    ) {
+      (Ok(name), Ok(value)) => {
+        response.headers_mut().insert(name, value);
+      }
+      _ => warn!("Invalid header {}: {}", name, value),
+    }
+  }
+  response
+}
+
+/// True if requests for one prefix can also be
+/// under the other (e.g. `/api` and `/api/v1`).
+pub fn prefixes_overlap(
+  a: &str,
+  b: &str,
+) -> bool {
+  let (a, b) =
+    (a.trim_end_matches('/'), b.trim_end_matches('/'));
+  let under = |path: &str, prefix: &str| {
+    path.strip_prefix(prefix).is_some_and(|rest| {
+      rest.is_empty() || rest.starts_with('/')
+    })
+  };
+  under(a, b) || under(b, a)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::builder::utils::{IgnoreRules, get_env};
+  use crate::mock_api::{
+    MockApi, MockRoute, RouteSettings,
+  };
+  use crate::status::BuildStatus;
+  use axum::body::Body;
+  use axum::middleware::from_fn_with_state;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+  use std::sync::Arc;
+  use tower::{Layer, ServiceExt};
+
+  #[rstest]
+  #[case("/api", "/api", true)]
+  #[case("/api/", "/api", true)]
+  #[case("/api/v1", "/api", true)]
+  #[case("/api", "/api/v1", true)]
+  #[case("/apis", "/api", false)]
+  #[case("/auth", "/api", false)]
+  fn prefixes_overlap_test(
+    #[case] a: &str,
+    #[case] b: &str,
+    #[case] expected: bool,
+  ) {
+    assert_eq!(expected, prefixes_overlap(a, b));
+  }
+
+  #[rstest]
+  #[case("/api/users/7", "user 7")]
+  #[case("/api/posts", "site")]
+  #[tokio::test]
+  async fn serve_mock_api_test(
+    #[case] path: &str,
+    #[case] expected: &str,
+  ) {
+    let file = std::env::temp_dir().join(format!(
+      "ssb-mock-api-{}-{}.txt",
+      std::process::id(),
+      expected.replace(' ', "")
+    ));
+    std::fs::write(&file, "user [@ params.id @]")
+      .unwrap();
+    let config = Config::default();
+    let status = BuildStatus::shared();
+    {
+      let mut status = status.lock().unwrap();
+      status.mock_api = MockApi {
+        routes: vec![MockRoute {
+          method: "GET".to_string(),
+          pattern: "/api/users/:id".to_string(),
+          file: file.clone(),
+          settings: RouteSettings::default(),
+        }],
+        errors: vec![],
+      };
+      status.mock_env = Some(Arc::new(get_env(
+        &config,
+        &IgnoreRules::new(&config),
+      )));
+    }
+    let app = from_fn_with_state(
+      MockApiState { config, status },
+      serve_mock_api,
+    )
+    .layer(
+      axum::Router::new().fallback(|| async { "site" }),
+    );
+    let response = app
+      .oneshot(
+        Request::builder()
+          .uri(path)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    let body =
+      to_bytes(response.into_body(), 1024).await.unwrap();
+    std::fs::remove_file(&file).unwrap();
+    assert_eq!(expected, String::from_utf8_lossy(&body));
+  }
+}
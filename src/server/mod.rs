@@ -1,63 +1,600 @@
+pub mod access_log;
+pub mod auth;
+pub mod base_path;
+pub mod client;
+pub mod forms;
+pub mod introspection;
+pub mod listing;
+pub mod mock_api;
+pub mod netlify_rules;
+pub mod passthrough;
+pub mod proxy;
+pub mod sync;
+pub mod throttle;
+pub mod tls;
+
+use self::access_log::*;
+use self::auth::*;
+use self::base_path::*;
+use self::client::*;
+use self::forms::*;
+use self::introspection::*;
+use self::listing::*;
+use self::mock_api::*;
+use self::netlify_rules::*;
+use self::passthrough::*;
+use self::proxy::*;
+use self::sync::*;
+use self::throttle::*;
+use self::tls::*;
 use crate::config::*;
-use anyhow::Result;
-use axum::Router;
+use crate::status::SharedStatus;
+use anyhow::{Context, Result};
+use axum::extract::Request;
+use axum::extract::connect_info::{
+  Connected, IntoMakeServiceWithConnectInfo,
+};
+use axum::http::header::{ACCEPT, ACCEPT_ENCODING};
+use axum::http::{StatusCode, Uri};
+use axum::middleware::from_fn_with_state;
 use axum::response::Html;
-use axum::routing::get;
+use axum::routing::{MethodRouter, any, get};
+use axum::serve::IncomingStream;
+use axum::{Router, ServiceExt};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tower::{Layer, ServiceBuilder};
+use tower_http::compression::CompressionLayer;
 use tower_http::services::ServeDir;
 use tower_livereload::LiveReloadLayer;
-use tracing::info;
+use tracing::{error, info, warn};
 
 pub struct Server {
   config: Config,
   port: u16,
+  status: SharedStatus,
+  client_events: ClientEvents,
 }
 
 impl Server {
   pub fn new(
     config: Config,
     port: u16,
+    status: SharedStatus,
+    client_events: ClientEvents,
   ) -> Server {
-    Server { config, port }
+    Server {
+      config,
+      port,
+      status,
+      client_events,
+    }
   }
 
+  /// Starts the server. The browser is opened once
+  /// the listener is bound and `first_build` fires.
   pub async fn start(
     &self,
     live_reload: LiveReloadLayer,
+    first_build: oneshot::Receiver<()>,
   ) -> Result<()> {
     info!("Starting web server");
-    launch_browser(self.port)?;
-    let service = ServeDir::new(&self.config.output_root)
-      .append_index_html_on_directories(true)
-      .not_found_service(get(missing_page));
-    let app = Router::new()
-      .fallback_service(service)
-      .layer(live_reload);
-    let listener = tokio::net::TcpListener::bind(
-      format!("127.0.0.1:{}", self.port),
+    let urls = server_urls(
+      self.config.bind,
+      self.port,
+      self.config.https,
+    );
+    let auth = AuthState::new(&self.config, urls.clone());
+    let not_found_page = self
+      .config
+      .output_root
+      .join(&self.config.not_found_page);
+    let listings_root = self
+      .config
+      .directory_listings
+      .then(|| self.config.output_root.clone());
+    let service = ServiceBuilder::new()
+      .map_request(skip_precompressed_html)
+      .service(site_files(
+        &self.config.output_root,
+        not_found_page,
+        listings_root,
+      ));
+    let mut app = capture_forms_layer(
+      self
+        .proxy_routes()
+        .merge(introspection_routes(self.status.clone()))
+        .merge(client_routes(self.client_events.clone()))
+        .merge(self.sync_routes())
+        .fallback_service(service),
+      FormsState {
+        config: self.config.clone(),
+        status: self.status.clone(),
+      },
     )
-    .await
-    .unwrap();
-    axum::serve(listener, app).await.unwrap();
+    .layer(from_fn_with_state(
+      MockApiState {
+        config: self.config.clone(),
+        status: self.status.clone(),
+      },
+      serve_mock_api,
+    ))
+    .layer(
+      live_reload
+        .custom_prefix(LIVE_RELOAD_PREFIX)
+        .request_predicate(no_live_reload_script),
+    )
+    .layer(from_fn_with_state(
+      ClientState {
+        sync: self.config.sync,
+        events: self.client_events.clone(),
+      },
+      inject_client,
+    ));
+    // Outside the live reload and client layers so
+    // pages are compressed after the scripts are
+    // added.
+    if self.config.compress {
+      app = app.layer(CompressionLayer::new());
+    }
+    // Outside compression so the bandwidth limit
+    // applies to the bytes that are sent.
+    if !self.config.throttle.is_empty() {
+      app = app.layer(from_fn_with_state(
+        ThrottleState::new(&self.config.throttle),
+        throttle,
+      ));
+    }
+    // Applied outside the router so rewrites
+    // happen before routing.
+    let app = from_fn_with_state(
+      NetlifyState {
+        status: self.status.clone(),
+        output_root: self.config.output_root.clone(),
+        passthrough: Passthrough::new(&self.config),
+      },
+      apply_netlify_rules,
+    )
+    .layer(app);
+    // Outside the Netlify rules so they see paths
+    // without the base path and their redirects get
+    // it added.
+    let base_path = self.config.base_prefix();
+    if !base_path.is_empty() {
+      info!("Serving the site under {}/", base_path);
+    }
+    let app = from_fn_with_state(
+      BasePathState {
+        base_path: base_path.clone(),
+        passthrough: Passthrough::new(&self.config),
+      },
+      mount_base_path,
+    )
+    .layer(app);
+    // Outside everything else but the access log
+    // so nothing is served before the
+    // request is let in.
+    let app =
+      from_fn_with_state(auth.clone(), require_auth)
+        .layer(app);
+    let app = from_fn_with_state(
+      AccessLogState {
+        status: self.status.clone(),
+      },
+      log_requests,
+    )
+    .layer(app);
+    let address =
+      SocketAddr::new(self.config.bind, self.port);
+    let listener =
+      TcpListener::bind(address).await.with_context(
+        || format!("Could not bind to {}", address),
+      )?;
+    info!("Preview server running at:");
+    urls.iter().for_each(|url| info!("  {}", url));
+    match &auth {
+      Some(auth) => {
+        auth.log_access(self.config.auth_token)
+      }
+      None if !self.config.bind.is_loopback() => info!(
+        "Anyone on the network can view the preview. Set auth_user and auth_password or auth_token to require a login"
+      ),
+      None => {}
+    }
+    if self.config.open_browser {
+      let command = self.config.browser.clone();
+      let url = format!(
+        "{}{}/{}",
+        urls[0].trim_end_matches('/'),
+        base_path,
+        self.config.open_page.trim_start_matches('/')
+      );
+      tokio::spawn(async move {
+        if first_build.await.is_ok()
+          && let Err(e) =
+            launch_browser(command.as_deref(), &url)
+        {
+          warn!("Could not open browser: {}", e);
+        }
+      });
+    }
+    let app = with_remote_addr(app);
+    if self.config.https {
+      let listener = TlsListener::new(
+        listener,
+        tls_acceptor(&self.config)?,
+      );
+      axum::serve(listener, app).await?;
+    } else {
+      axum::serve(listener, app).await?;
+    }
     Ok(())
   }
 }
 
-fn launch_browser(port: u16) -> Result<()> {
-  if !cfg!(debug_assertions) {
-    let args: Vec<String> =
-      vec![format!("http://localhost:{}", port)];
-    Command::new("open").args(args).output()?;
+impl Server {
+  // Each prefix and everything under it goes to
+  // its upstream. Everything else falls through
+  // to the site.
+  fn proxy_routes(&self) -> Router {
+    let mut router = Router::new();
+    for (prefix, upstream) in &self.config.proxy {
+      if let Err(e) = check_proxy_prefix(prefix) {
+        error!("{}", e);
+        continue;
+      }
+      let prefix = prefix.trim_end_matches('/');
+      let handler = any(proxy_request).with_state(
+        ProxyState::new(upstream, &self.config),
+      );
+      info!("Proxying {} to {}", prefix, upstream);
+      if self
+        .config
+        .content_root
+        .join(&self.config.api_dir)
+        .is_dir()
+        && prefixes_overlap(
+          prefix,
+          &self.config.api_prefix,
+        )
+      {
+        warn!(
+          "Mock API routes under {} are answered before the proxy for {}. Change api_prefix to keep them apart",
+          self.config.api_prefix, prefix
+        );
+      }
+      router = router
+        .route(prefix, handler.clone())
+        .route(&format!("{}/{{*path}}", prefix), handler);
+    }
+    router
   }
+
+  // Only added when sync is on so pages don't
+  // load the script otherwise.
+  fn sync_routes(&self) -> Router {
+    if self.config.sync {
+      info!("Syncing scrolling and navigation");
+      sync_routes(SyncState::new())
+    } else {
+      Router::new()
+    }
+  }
+}
+
+/// The address a request came from. It works
+/// with both the plain and TLS listeners.
+#[derive(Clone, Copy, Debug)]
+pub struct RemoteAddr(pub SocketAddr);
+
+impl Connected<IncomingStream<'_, TcpListener>>
+  for RemoteAddr
+{
+  fn connect_info(
+    stream: IncomingStream<'_, TcpListener>
+  ) -> RemoteAddr {
+    RemoteAddr(*stream.remote_addr())
+  }
+}
+
+impl Connected<IncomingStream<'_, TlsListener>>
+  for RemoteAddr
+{
+  fn connect_info(
+    stream: IncomingStream<'_, TlsListener>
+  ) -> RemoteAddr {
+    RemoteAddr(*stream.remote_addr())
+  }
+}
+
+// Lets handlers see the address each request
+// came from.
+fn with_remote_addr<S>(
+  app: S
+) -> IntoMakeServiceWithConnectInfo<S, RemoteAddr>
+where
+  S: ServiceExt<Request>,
+{
+  app.into_make_service_with_connect_info()
+}
+
+/// Lists the URLs the server can be reached at.
+/// When bound to all interfaces that includes the
+/// address of each network interface so other
+/// devices on the LAN can connect.
+pub fn server_urls(
+  bind: IpAddr,
+  port: u16,
+  https: bool,
+) -> Vec<String> {
+  let scheme = if https { "https" } else { "http" };
+  if bind.is_loopback() {
+    vec![format!("{}://localhost:{}/", scheme, port)]
+  } else if bind.is_unspecified() {
+    let mut urls =
+      vec![format!("{}://localhost:{}/", scheme, port)];
+    if let Ok(interfaces) = if_addrs::get_if_addrs() {
+      urls.extend(
+        interfaces
+          .iter()
+          .filter(|interface| {
+            !interface.is_loopback()
+              && (bind.is_ipv6()
+                || interface.ip().is_ipv4())
+          })
+          .map(|interface| {
+            ip_url(scheme, interface.ip(), port)
+          }),
+      );
+    }
+    urls
+  } else {
+    vec![ip_url(scheme, bind, port)]
+  }
+}
+
+fn ip_url(
+  scheme: &str,
+  ip: IpAddr,
+  port: u16,
+) -> String {
+  match ip {
+    IpAddr::V4(ip) => {
+      format!("{}://{}:{}/", scheme, ip, port)
+    }
+    IpAddr::V6(ip) => {
+      format!("{}://[{}]:{}/", scheme, ip, port)
+    }
+  }
+}
+
+fn launch_browser(
+  command: Option<&str>,
+  url: &str,
+) -> Result<()> {
+  info!("Opening {}", url);
+  let parts = browser_command(command, url);
+  let mut child =
+    Command::new(&parts[0]).args(&parts[1..]).spawn()?;
+  // Waited on so it doesn't linger as a zombie
+  // once it exits.
+  std::thread::spawn(move || match child.wait() {
+    Ok(status) if !status.success() => {
+      warn!("{} exited with {}", parts[0], status)
+    }
+    Ok(_) => {}
+    Err(e) => warn!("{}: {}", parts[0], e),
+  });
   Ok(())
 }
 
-async fn missing_page() -> Html<&'static str> {
-  Html(
-    r#"<!DOCTYPE html>
+/// Builds the command that opens the browser. A
+/// configured command has `{url}` replaced (or the
+/// URL added to the end if it's not there).
+/// Otherwise the platform's default opener is used.
+pub fn browser_command(
+  command: Option<&str>,
+  url: &str,
+) -> Vec<String> {
+  match command {
+    Some(command) if !command.trim().is_empty() => {
+      let mut parts: Vec<String> = command
+        .split_whitespace()
+        .map(|part| part.replace("{url}", url))
+        .collect();
+      if !command.contains("{url}") {
+        parts.push(url.to_string());
+      }
+      parts
+    }
+    _ => {
+      if cfg!(target_os = "macos") {
+        vec!["open".to_string(), url.to_string()]
+      } else if cfg!(target_os = "windows") {
+        vec![
+          "cmd".to_string(),
+          "/C".to_string(),
+          "start".to_string(),
+          "".to_string(),
+          url.to_string(),
+        ]
+      } else {
+        vec!["xdg-open".to_string(), url.to_string()]
+      }
+    }
+  }
+}
+
+// Pages get the client script instead of the
+// live reload one. It uses the same long poll but
+// can swap stylesheets rather than reload.
+fn no_live_reload_script(_: &Request) -> bool {
+  false
+}
+
+// The live reload script can't be added to a
+// precompressed page so pages that browsers
+// navigate to get the plain file. The compression
+// layer still compresses them on the way out.
+fn skip_precompressed_html(
+  mut request: Request
+) -> Request {
+  let is_page = request
+    .headers()
+    .get(ACCEPT)
+    .and_then(|accept| accept.to_str().ok())
+    .is_some_and(|accept| accept.contains("text/html"));
+  if is_page {
+    request.headers_mut().remove(ACCEPT_ENCODING);
+  }
+  request
+}
+
+/// Serves the output folder. Missing files go to
+/// `missing_page` through a plain fallback (rather
+/// than `not_found_service`, which would make
+/// every fallback response a 404) so directory
+/// listings keep their 200.
+fn site_files(
+  output_root: &Path,
+  not_found_page: PathBuf,
+  listings_root: Option<PathBuf>,
+) -> ServeDir<MethodRouter> {
+  ServeDir::new(output_root)
+    .append_index_html_on_directories(true)
+    .precompressed_br()
+    .precompressed_gzip()
+    .fallback(get(move |uri: Uri| {
+      missing_page(
+        not_found_page.clone(),
+        listings_root.clone(),
+        uri,
+      )
+    }))
+}
+
+// Lists the files in folders without an index
+// page when listings are turned on. Otherwise
+// serves the site's own 404 page if it has one.
+async fn missing_page(
+  not_found_page: PathBuf,
+  listings_root: Option<PathBuf>,
+  uri: Uri,
+) -> (StatusCode, Html<String>) {
+  if let Some(output_root) = listings_root
+    && uri.path().ends_with('/')
+    && let Some(dir) =
+      listing_dir(&output_root, uri.path())
+  {
+    match directory_listing(uri.path(), &dir) {
+      Ok(html) => return (StatusCode::OK, Html(html)),
+      Err(e) => {
+        error!("Could not list {}: {}", uri.path(), e)
+      }
+    }
+  }
+  let body = tokio::fs::read_to_string(&not_found_page)
+    .await
+    .unwrap_or_else(|_| {
+      r#"<!DOCTYPE html>
 <html lang="en">
 <head><style>body { background: black; color: white;}</style></head>
-<body>Page Not Found</sody>
-</html>"#,
-  )
+<body>Page Not Found</body>
+</html>"#
+        .to_string()
+    });
+  (StatusCode::NOT_FOUND, Html(body))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+  use tower::ServiceExt as _;
+
+  #[rstest]
+  #[case(
+    Some("firefox --new-tab {url}"),
+    vec!["firefox", "--new-tab", "http://localhost:5444/"]
+  )]
+  #[case(
+    Some("open -a Safari"),
+    vec!["open", "-a", "Safari", "http://localhost:5444/"]
+  )]
+  fn browser_command_test(
+    #[case] command: Option<&str>,
+    #[case] expected: Vec<&str>,
+  ) {
+    assert_eq!(
+      expected,
+      browser_command(command, "http://localhost:5444/")
+    );
+  }
+
+  #[test]
+  fn browser_command_default_test() {
+    let got =
+      browser_command(None, "http://localhost:5444/");
+    assert_eq!(
+      Some(&"http://localhost:5444/".to_string()),
+      got.last()
+    );
+  }
+
+  #[rstest]
+  #[case("127.0.0.1", vec!["http://localhost:5444/"])]
+  #[case("192.168.1.20", vec!["http://192.168.1.20:5444/"])]
+  #[case("::1", vec!["http://localhost:5444/"])]
+  fn server_urls_test(
+    #[case] bind: &str,
+    #[case] expected: Vec<&str>,
+  ) {
+    assert_eq!(
+      expected,
+      server_urls(bind.parse().unwrap(), 5444, false)
+    );
+  }
+
+  #[rstest]
+  #[case("127.0.0.1", vec!["https://localhost:5444/"])]
+  #[case("::1", vec!["https://localhost:5444/"])]
+  #[case("fe80::1", vec!["https://[fe80::1]:5444/"])]
+  fn server_urls_https_test(
+    #[case] bind: &str,
+    #[case] expected: Vec<&str>,
+  ) {
+    assert_eq!(
+      expected,
+      server_urls(bind.parse().unwrap(), 5444, true)
+    );
+  }
+
+  #[rstest]
+  #[case("/src/", StatusCode::OK)]
+  #[case("/src/lib.rs", StatusCode::OK)]
+  #[case("/missing/", StatusCode::NOT_FOUND)]
+  #[tokio::test]
+  async fn site_files_test(
+    #[case] path: &str,
+    #[case] expected: StatusCode,
+  ) {
+    let response = site_files(
+      Path::new("."),
+      PathBuf::from("missing.html"),
+      Some(PathBuf::from(".")),
+    )
+    .oneshot(
+      Request::builder()
+        .uri(path)
+        .body(axum::body::Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(expected, response.status());
+  }
 }
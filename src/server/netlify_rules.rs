@@ -0,0 +1,204 @@
+use crate::server::passthrough::Passthrough;
+use crate::status::SharedStatus;
+use axum::extract::{Request, State};
+use axum::http::header::LOCATION;
+use axum::http::{
+  HeaderName, HeaderValue, StatusCode, Uri,
+};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+#[derive(Clone, Debug)]
+pub struct NetlifyState {
+  pub status: SharedStatus,
+  pub output_root: PathBuf,
+  pub passthrough: Passthrough,
+}
+
+/// Applies redirects, rewrites and custom headers
+/// the way Netlify would. The rules come from the
+/// last build. The `passthrough` prefixes aren't
+/// part of the site so the rules don't apply to
+/// them.
+pub async fn apply_netlify_rules(
+  State(state): State<NetlifyState>,
+  mut request: Request,
+  next: Next,
+) -> Response {
+  if state.passthrough.contains(request.uri().path()) {
+    return next.run(request).await;
+  }
+  let rules =
+    state.status.lock().unwrap().netlify_rules.clone();
+  let path = request.uri().path().to_string();
+  let query =
+    request.uri().query().map(|q| q.to_string());
+  let redirect =
+    rules.find_redirect(&path).filter(|(redirect, _)| {
+      redirect.force
+        || !file_exists(&state.output_root, &path)
+    });
+  let mut response = match redirect {
+    Some((redirect, target))
+      if redirect.status == 200
+        && target.contains("://") =>
+    {
+      warn!(
+        "Proxying {} to {} isn't supported in the preview server",
+        path, target
+      );
+      next.run(request).await
+    }
+    Some((redirect, target))
+      if (300..400).contains(&redirect.status) =>
+    {
+      (
+        StatusCode::from_u16(redirect.status).unwrap(),
+        [(
+          LOCATION,
+          with_query(&target, query.as_deref()),
+        )],
+      )
+        .into_response()
+    }
+    Some((redirect, target)) => {
+      match with_query(&target, query.as_deref())
+        .parse::<Uri>()
+      {
+        Ok(uri) => {
+          *request.uri_mut() = uri;
+          let mut response = next.run(request).await;
+          if let Ok(status) =
+            StatusCode::from_u16(redirect.status)
+            && redirect.status != 200
+          {
+            *response.status_mut() = status;
+          }
+          response
+        }
+        Err(e) => {
+          warn!(
+            "Invalid rewrite target {}: {}",
+            target, e
+          );
+          next.run(request).await
+        }
+      }
+    }
+    None => next.run(request).await,
+  };
+  for (name, value) in rules.headers_for(&path) {
+    match (
+      HeaderName::from_bytes(name.as_bytes()),
+      HeaderValue::from_str(&value),
+    ) {
+      (Ok(name), Ok(value)) => {
+        response.headers_mut().insert(name, value);
+      }
+      _ => warn!("Invalid header {}: {}", name, value),
+    }
+  }
+  response
+}
+
+pub fn file_exists(
+  output_root: &Path,
+  path: &str,
+) -> bool {
+  let candidate =
+    output_root.join(path.trim_start_matches('/'));
+  candidate.is_file()
+    || candidate.join("index.html").is_file()
+}
+
+fn with_query(
+  target: &str,
+  query: Option<&str>,
+) -> String {
+  match query {
+    Some(query) if !target.contains('?') => {
+      format!("{}?{}", target, query)
+    }
+    _ => target.to_string(),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::config::Config;
+  use crate::netlify::NetlifyRules;
+  use crate::status::BuildStatus;
+  use axum::Router;
+  use axum::body::{Body, to_bytes};
+  use axum::middleware::from_fn_with_state;
+  use axum::routing::get;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+  use std::fs;
+  use std::sync::Arc;
+  use tower::{Layer, ServiceExt};
+
+  #[rstest]
+  #[case("/__ssb/status", "status")]
+  #[case("/tower-livereload/long-poll", "long-poll")]
+  #[case("/api/users", "api")]
+  #[case("/about/", "/index.html")]
+  #[tokio::test]
+  async fn apply_netlify_rules_passthrough_test(
+    #[case] path: &str,
+    #[case] expected: &str,
+  ) {
+    let output_root = std::env::temp_dir().join(format!(
+      "ssb-netlify-{}-{}",
+      std::process::id(),
+      expected.replace('/', "")
+    ));
+    fs::create_dir_all(&output_root).unwrap();
+    fs::write(
+      output_root.join("_redirects"),
+      "/* /index.html 200\n",
+    )
+    .unwrap();
+    let router = Router::new()
+      .route("/__ssb/status", get(|| async { "status" }))
+      .route(
+        "/tower-livereload/long-poll",
+        get(|| async { "long-poll" }),
+      )
+      .route("/api/users", get(|| async { "api" }))
+      .fallback(|uri: Uri| async move {
+        uri.path().to_string()
+      });
+    let status = BuildStatus::shared();
+    status.lock().unwrap().netlify_rules =
+      Arc::new(NetlifyRules::load(
+        &output_root.join("netlify.toml"),
+        &output_root,
+      ));
+    let app = from_fn_with_state(
+      NetlifyState {
+        status,
+        output_root: output_root.clone(),
+        passthrough: Passthrough::new(&Config::default()),
+      },
+      apply_netlify_rules,
+    )
+    .layer(router);
+    let response = app
+      .oneshot(
+        Request::builder()
+          .uri(path)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    let body =
+      to_bytes(response.into_body(), 1024).await.unwrap();
+    fs::remove_dir_all(&output_root).unwrap();
+    assert_eq!(expected, String::from_utf8_lossy(&body));
+  }
+}
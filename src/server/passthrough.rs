@@ -0,0 +1,105 @@
+use crate::config::Config;
+use crate::server::introspection::INTROSPECTION_PREFIX;
+
+/// Where the live reload script polls
+pub const LIVE_RELOAD_PREFIX: &str = "/tower-livereload";
+
+/// The preview server's own endpoints
+pub const SERVER_PREFIXES: &[&str] =
+  &[INTROSPECTION_PREFIX, LIVE_RELOAD_PREFIX];
+
+/// The prefixes that aren't part of the site: the
+/// server's own endpoints, the proxies and the
+/// mock API. The base path and Netlify rules
+/// leave them alone.
+#[derive(Clone, Debug, Default)]
+pub struct Passthrough {
+  prefixes: Vec<String>,
+}
+
+impl Passthrough {
+  pub fn new(config: &Config) -> Passthrough {
+    Passthrough {
+      prefixes: SERVER_PREFIXES
+        .iter()
+        .map(|prefix| prefix.to_string())
+        .chain(config.proxy.keys().cloned())
+        .chain([config.api_prefix.clone()])
+        .collect(),
+    }
+  }
+
+  pub fn contains(
+    &self,
+    path: &str,
+  ) -> bool {
+    self
+      .prefixes
+      .iter()
+      .any(|prefix| is_under(path, prefix))
+  }
+}
+
+/// True for the server's own endpoints, which
+/// throttling shouldn't slow down or break.
+pub fn is_server_path(path: &str) -> bool {
+  SERVER_PREFIXES
+    .iter()
+    .any(|prefix| is_under(path, prefix))
+}
+
+fn is_under(
+  path: &str,
+  prefix: &str,
+) -> bool {
+  let prefix = prefix.trim_end_matches('/');
+  !prefix.is_empty()
+    && path.strip_prefix(prefix).is_some_and(|rest| {
+      rest.is_empty() || rest.starts_with('/')
+    })
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+  use std::collections::BTreeMap;
+
+  #[rstest]
+  #[case("/__ssb/status", true)]
+  #[case("/tower-livereload/long-poll", true)]
+  #[case("/api", true)]
+  #[case("/api/users/1", true)]
+  #[case("/backend/login", true)]
+  #[case("/apis/", false)]
+  #[case("/about/", false)]
+  fn passthrough_test(
+    #[case] path: &str,
+    #[case] expected: bool,
+  ) {
+    let config = Config {
+      proxy: BTreeMap::from([(
+        "/backend/".to_string(),
+        "http://localhost:3000".to_string(),
+      )]),
+      ..Config::default()
+    };
+    assert_eq!(
+      expected,
+      Passthrough::new(&config).contains(path)
+    );
+  }
+
+  #[rstest]
+  #[case("/__ssb", true)]
+  #[case("/tower-livereload/back-up", true)]
+  #[case("/api/users", false)]
+  #[case("/tower-livereloaded", false)]
+  fn is_server_path_test(
+    #[case] path: &str,
+    #[case] expected: bool,
+  ) {
+    assert_eq!(expected, is_server_path(path));
+  }
+}
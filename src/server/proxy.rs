@@ -0,0 +1,316 @@
+use crate::config::Config;
+use crate::server::auth::basic_credentials;
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::header::{
+  AUTHORIZATION, CONNECTION, HOST, UPGRADE,
+};
+use axum::http::{
+  HeaderMap, HeaderValue, StatusCode, Uri,
+};
+use axum::response::{IntoResponse, Response};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use tracing::warn;
+
+/// Headers that only apply to one connection so
+/// they aren't passed on. Headers named in
+/// `Connection` are dropped too.
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+  "connection",
+  "keep-alive",
+  "proxy-authenticate",
+  "proxy-authorization",
+  "proxy-connection",
+  "te",
+  "trailer",
+  "transfer-encoding",
+  "upgrade",
+];
+
+#[derive(Clone, Debug)]
+pub struct ProxyState {
+  pub upstream: String,
+  pub forwarded_proto: &'static str,
+  /// The preview's own basic auth header which
+  /// isn't sent upstream
+  pub preview_auth: Option<String>,
+  pub client: Client<HttpConnector, Body>,
+}
+
+impl ProxyState {
+  pub fn new(
+    upstream: &str,
+    config: &Config,
+  ) -> ProxyState {
+    ProxyState {
+      upstream: upstream
+        .trim_end_matches('/')
+        .to_string(),
+      forwarded_proto: if config.https {
+        "https"
+      } else {
+        "http"
+      },
+      preview_auth: basic_credentials(config),
+      client: Client::builder(TokioExecutor::new())
+        .build_http(),
+    }
+  }
+}
+
+/// Checks a proxy prefix can be used as a route.
+pub fn check_proxy_prefix(
+  prefix: &str
+) -> Result<(), String> {
+  if !prefix.starts_with('/') || prefix == "/" {
+    Err(format!(
+      "Proxy prefix {} needs to start with / and can't be the whole site",
+      prefix
+    ))
+  } else if prefix.contains(['{', '}', '*']) {
+    Err(format!(
+      "Proxy prefix {} can't contain {{, }} or *",
+      prefix
+    ))
+  } else {
+    Ok(())
+  }
+}
+
+/// Sends a request on to the upstream server with
+/// its path unchanged and passes back the response.
+/// Websocket upgrades are connected through once
+/// the upstream accepts them.
+pub async fn proxy_request(
+  State(state): State<ProxyState>,
+  mut request: Request,
+) -> Response {
+  let path = request
+    .uri()
+    .path_and_query()
+    .map(|path| path.as_str().to_string())
+    .unwrap_or("/".to_string());
+  let target = format!("{}{}", state.upstream, path);
+  let uri = match target.parse::<Uri>() {
+    Ok(uri) => uri,
+    Err(e) => {
+      warn!("Invalid proxy target {}: {}", target, e);
+      return StatusCode::BAD_GATEWAY.into_response();
+    }
+  };
+  let client_upgrade = request
+    .headers()
+    .contains_key(UPGRADE)
+    .then(|| hyper::upgrade::on(&mut request));
+  upstream_headers(
+    request.headers_mut(),
+    state.preview_auth.as_deref(),
+  );
+  if let Some(host) = request.headers().get(HOST).cloned()
+  {
+    request
+      .headers_mut()
+      .insert("x-forwarded-host", host);
+  }
+  request.headers_mut().insert(
+    "x-forwarded-proto",
+    HeaderValue::from_static(state.forwarded_proto),
+  );
+  *request.uri_mut() = uri;
+  match state.client.request(request).await {
+    Ok(mut response) => {
+      if response.status()
+        == StatusCode::SWITCHING_PROTOCOLS
+        && let Some(client_upgrade) = client_upgrade
+      {
+        let upstream_upgrade =
+          hyper::upgrade::on(&mut response);
+        tokio::spawn(async move {
+          match (
+            client_upgrade.await,
+            upstream_upgrade.await,
+          ) {
+            (Ok(client), Ok(upstream)) => {
+              let _ = tokio::io::copy_bidirectional(
+                &mut TokioIo::new(client),
+                &mut TokioIo::new(upstream),
+              )
+              .await;
+            }
+            (Err(e), _) | (_, Err(e)) => {
+              warn!("Proxied upgrade failed: {}", e)
+            }
+          }
+        });
+      }
+      let upgraded = response.status()
+        == StatusCode::SWITCHING_PROTOCOLS;
+      strip_hop_by_hop(response.headers_mut(), upgraded);
+      response.map(Body::new)
+    }
+    Err(e) => {
+      warn!("Could not reach {}: {}", target, e);
+      (
+        StatusCode::BAD_GATEWAY,
+        format!("Could not reach {}", target),
+      )
+        .into_response()
+    }
+  }
+}
+
+/// Gets a request's headers ready to send
+/// upstream. The connection headers for websocket
+/// upgrades are kept.
+pub fn upstream_headers(
+  headers: &mut HeaderMap,
+  preview_auth: Option<&str>,
+) {
+  let upgrade = headers.contains_key(UPGRADE);
+  strip_hop_by_hop(headers, upgrade);
+  if let Some(preview_auth) = preview_auth
+    && headers
+      .get(AUTHORIZATION)
+      .is_some_and(|value| value == preview_auth)
+  {
+    headers.remove(AUTHORIZATION);
+  }
+}
+
+/// Removes the hop-by-hop headers. `Connection`
+/// and `Upgrade` stay for upgrades since they're
+/// how one is asked for and accepted.
+pub fn strip_hop_by_hop(
+  headers: &mut HeaderMap,
+  upgrade: bool,
+) {
+  let named = headers
+    .get_all(CONNECTION)
+    .iter()
+    .filter_map(|value| value.to_str().ok())
+    .flat_map(|value| value.split(','))
+    .map(|name| name.trim().to_lowercase())
+    .filter(|name| !name.is_empty())
+    .collect::<Vec<_>>();
+  let upgrade = upgrade
+    .then(|| headers.get(UPGRADE).cloned())
+    .flatten();
+  HOP_BY_HOP_HEADERS
+    .iter()
+    .map(|name| name.to_string())
+    .chain(named)
+    .for_each(|name| {
+      headers.remove(name.as_str());
+    });
+  if let Some(upgrade) = upgrade {
+    headers.insert(
+      CONNECTION,
+      HeaderValue::from_static("upgrade"),
+    );
+    headers.insert(UPGRADE, upgrade);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("/api", true)]
+  #[case("/auth/v2", true)]
+  #[case("/", false)]
+  #[case("api", false)]
+  #[case("/api/{id}", false)]
+  fn check_proxy_prefix_test(
+    #[case] prefix: &str,
+    #[case] expected: bool,
+  ) {
+    assert_eq!(
+      expected,
+      check_proxy_prefix(prefix).is_ok()
+    );
+  }
+
+  fn headers(
+    pairs: &[(&'static str, &'static str)]
+  ) -> HeaderMap {
+    pairs
+      .iter()
+      .map(|(name, value)| {
+        (
+          axum::http::HeaderName::from_static(name),
+          HeaderValue::from_static(value),
+        )
+      })
+      .collect()
+  }
+
+  #[test]
+  fn strip_hop_by_hop_test() {
+    let mut got = headers(&[
+      ("connection", "keep-alive, x-trace"),
+      ("keep-alive", "timeout=5"),
+      ("te", "trailers"),
+      ("trailer", "expires"),
+      ("transfer-encoding", "chunked"),
+      ("proxy-authorization", "Basic abc"),
+      ("x-trace", "1"),
+      ("content-type", "text/html"),
+    ]);
+    strip_hop_by_hop(&mut got, false);
+    assert_eq!(
+      headers(&[("content-type", "text/html")]),
+      got
+    );
+  }
+
+  #[test]
+  fn strip_hop_by_hop_upgrade_test() {
+    let mut got = headers(&[
+      ("connection", "keep-alive, Upgrade"),
+      ("upgrade", "websocket"),
+      ("keep-alive", "timeout=5"),
+      ("sec-websocket-key", "abc"),
+    ]);
+    strip_hop_by_hop(&mut got, true);
+    assert_eq!(
+      headers(&[
+        ("connection", "upgrade"),
+        ("upgrade", "websocket"),
+        ("sec-websocket-key", "abc"),
+      ]),
+      got
+    );
+  }
+
+  #[rstest]
+  #[case(Some("Basic YWxmYTpicmF2bw=="), None)]
+  #[case(Some("Bearer abc"), Some("Bearer abc"))]
+  #[case(None, None)]
+  fn upstream_headers_test(
+    #[case] authorization: Option<&'static str>,
+    #[case] expected: Option<&'static str>,
+  ) {
+    let mut got = HeaderMap::new();
+    if let Some(authorization) = authorization {
+      got.insert(
+        AUTHORIZATION,
+        HeaderValue::from_static(authorization),
+      );
+    }
+    upstream_headers(
+      &mut got,
+      Some("Basic YWxmYTpicmF2bw=="),
+    );
+    assert_eq!(
+      expected,
+      got
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+    );
+  }
+}
@@ -0,0 +1,131 @@
+// Added to pages when the preview server runs with
+// sync on. Scrolling, link clicks and navigation in
+// one browser are sent through the server to all
+// the others.
+(() => {
+  const scheme = location.protocol === "https:" ? "wss" : "ws";
+  // Set before following another browser so the
+  // page that opens doesn't send itself back.
+  const followedKey = "ssb-sync-followed";
+  let socket;
+  let remoteScroll = false;
+  let scrollQueued = false;
+
+  function connect() {
+    socket = new WebSocket(`${scheme}://${location.host}/__ssb/sync`);
+    socket.addEventListener("message", (message) => {
+      receive(JSON.parse(message.data));
+    });
+    socket.addEventListener("close", () => {
+      setTimeout(connect, 1000);
+    });
+  }
+
+  function send(message) {
+    if (socket.readyState === WebSocket.OPEN) {
+      socket.send(JSON.stringify(message));
+    }
+  }
+
+  function currentUrl() {
+    return location.pathname + location.search + location.hash;
+  }
+
+  function receive(message) {
+    if (message.type === "navigate" && message.url !== currentUrl()) {
+      sessionStorage.setItem(followedKey, message.url);
+      location.assign(message.url);
+    } else if (
+      message.type === "scroll" &&
+      message.path === location.pathname
+    ) {
+      const x = message.x * maxScroll("Width");
+      const y = message.y * maxScroll("Height");
+      if (x !== scrollX || y !== scrollY) {
+        remoteScroll = true;
+        scrollTo(x, y);
+      }
+    }
+  }
+
+  // Pages opened by following another browser or
+  // by a reload stay put. Anything else (typing a
+  // URL, going back) brings the others along.
+  function announce() {
+    if (sessionStorage.getItem(followedKey) === currentUrl()) {
+      sessionStorage.removeItem(followedKey);
+      return;
+    }
+    const [entry] = performance.getEntriesByType("navigation");
+    if (entry?.type !== "reload") {
+      send({ type: "navigate", url: currentUrl() });
+    }
+  }
+
+  function maxScroll(dimension) {
+    return Math.max(
+      document.documentElement[`scroll${dimension}`] -
+        window[`inner${dimension}`],
+      0,
+    );
+  }
+
+  // Positions are sent as a fraction of how far the
+  // page can scroll so they line up across screen
+  // sizes.
+  function sendScroll() {
+    const width = maxScroll("Width");
+    const height = maxScroll("Height");
+    send({
+      type: "scroll",
+      path: location.pathname,
+      x: width > 0 ? scrollX / width : 0,
+      y: height > 0 ? scrollY / height : 0,
+    });
+  }
+
+  addEventListener(
+    "scroll",
+    () => {
+      if (remoteScroll) {
+        remoteScroll = false;
+        return;
+      }
+      if (!scrollQueued) {
+        scrollQueued = true;
+        requestAnimationFrame(() => {
+          scrollQueued = false;
+          sendScroll();
+        });
+      }
+    },
+    { passive: true },
+  );
+
+  document.addEventListener("click", (event) => {
+    const link = event.target.closest?.("a[href]");
+    if (
+      !link ||
+      event.defaultPrevented ||
+      event.button !== 0 ||
+      event.metaKey ||
+      event.ctrlKey ||
+      event.shiftKey ||
+      event.altKey ||
+      (link.target && link.target !== "_self")
+    ) {
+      return;
+    }
+    const url = new URL(link.href);
+    if (url.origin === location.origin) {
+      const path = url.pathname + url.search + url.hash;
+      sessionStorage.setItem(followedKey, path);
+      send({ type: "navigate", url: path });
+    }
+  });
+
+  addEventListener("hashchange", announce);
+
+  connect();
+  socket.addEventListener("open", announce, { once: true });
+})();
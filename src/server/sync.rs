@@ -0,0 +1,195 @@
+use crate::server::introspection::INTROSPECTION_PREFIX;
+use axum::Router;
+use axum::extract::State;
+use axum::extract::ws::{
+  Message, WebSocket, WebSocketUpgrade,
+};
+use axum::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+/// What one browser sends to be mirrored in the
+/// others.
+#[derive(
+  Clone, Debug, Deserialize, PartialEq, Serialize,
+)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SyncMessage {
+  /// Scroll position as a fraction of how far the
+  /// page at `path` can scroll
+  Scroll { path: String, x: f64, y: f64 },
+  /// A path (with any query and hash) on the site
+  Navigate { url: String },
+}
+
+impl SyncMessage {
+  /// Reads a message from a browser. Navigation
+  /// has to stay on the site.
+  pub fn parse(text: &str) -> Option<SyncMessage> {
+    match serde_json::from_str(text).ok()? {
+      SyncMessage::Navigate { url }
+        if !url.starts_with('/')
+          || url.starts_with("//") =>
+      {
+        None
+      }
+      message => Some(message),
+    }
+  }
+}
+
+/// Each browser gets an ID so its own messages
+/// aren't sent back to it.
+#[derive(Clone, Debug)]
+pub struct SyncState {
+  messages: broadcast::Sender<(u64, SyncMessage)>,
+  next_id: Arc<AtomicU64>,
+}
+
+impl SyncState {
+  pub fn new() -> SyncState {
+    SyncState {
+      messages: broadcast::channel(64).0,
+      next_id: Arc::new(AtomicU64::new(1)),
+    }
+  }
+}
+
+impl Default for SyncState {
+  fn default() -> SyncState {
+    SyncState::new()
+  }
+}
+
+const SYNC_SCRIPT: &str = include_str!("sync.js");
+
+/// The script synced pages load and the websocket
+/// it connects to.
+pub fn sync_routes(state: SyncState) -> Router {
+  let path = |name: &str| {
+    format!("{}/{}", INTROSPECTION_PREFIX, name)
+  };
+  Router::new()
+    .route(&path("sync.js"), get(sync_script))
+    .route(&path("sync"), get(sync_socket))
+    .with_state(state)
+}
+
+async fn sync_script() -> Response {
+  (
+    [
+      (CONTENT_TYPE, "text/javascript"),
+      (CACHE_CONTROL, "no-cache"),
+    ],
+    SYNC_SCRIPT,
+  )
+    .into_response()
+}
+
+async fn sync_socket(
+  State(state): State<SyncState>,
+  upgrade: WebSocketUpgrade,
+) -> Response {
+  upgrade.on_upgrade(move |socket| relay(socket, state))
+}
+
+// Passes messages from this browser to the others
+// and theirs to it until either side closes.
+async fn relay(
+  socket: WebSocket,
+  state: SyncState,
+) {
+  let id = state.next_id.fetch_add(1, Ordering::Relaxed);
+  info!("Sync browser {} connected", id);
+  let (mut sender, mut receiver) = socket.split();
+  let mut messages = state.messages.subscribe();
+  let mut outgoing = tokio::spawn(async move {
+    loop {
+      match messages.recv().await {
+        Ok((from, message)) if from != id => {
+          let Ok(text) = serde_json::to_string(&message)
+          else {
+            continue;
+          };
+          if sender
+            .send(Message::Text(text.into()))
+            .await
+            .is_err()
+          {
+            break;
+          }
+        }
+        Ok(_) | Err(RecvError::Lagged(_)) => continue,
+        Err(RecvError::Closed) => break,
+      }
+    }
+  });
+  let messages = state.messages.clone();
+  let mut incoming = tokio::spawn(async move {
+    while let Some(Ok(message)) = receiver.next().await {
+      if let Message::Text(text) = message {
+        match SyncMessage::parse(&text) {
+          Some(message) => {
+            let _ = messages.send((id, message));
+          }
+          None => {
+            warn!(
+              "Ignoring sync message: {}",
+              text.as_str()
+            )
+          }
+        }
+      }
+    }
+  });
+  tokio::select! {
+    _ = &mut outgoing => incoming.abort(),
+    _ = &mut incoming => outgoing.abort(),
+  }
+  info!("Sync browser {} disconnected", id);
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case(
+    r#"{"type":"navigate","url":"/about/#team"}"#,
+    Some(SyncMessage::Navigate {
+      url: "/about/#team".to_string()
+    })
+  )]
+  #[case(
+    r#"{"type":"scroll","path":"/","x":0,"y":0.5}"#,
+    Some(SyncMessage::Scroll {
+      path: "/".to_string(),
+      x: 0.0,
+      y: 0.5
+    })
+  )]
+  #[case(
+    r#"{"type":"navigate","url":"https://example.com/"}"#,
+    None
+  )]
+  #[case(
+    r#"{"type":"navigate","url":"//example.com/"}"#,
+    None
+  )]
+  #[case(r#"{"type":"click"}"#, None)]
+  fn parse_test(
+    #[case] text: &str,
+    #[case] expected: Option<SyncMessage>,
+  ) {
+    assert_eq!(expected, SyncMessage::parse(text));
+  }
+}
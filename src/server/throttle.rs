@@ -0,0 +1,341 @@
+use crate::server::passthrough::is_server_path;
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use futures_util::StreamExt;
+use futures_util::stream;
+use globset::{Glob, GlobMatcher};
+use serde::Deserialize;
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Slows down or fails requests to the preview
+/// server. A rule without a `path` applies to every
+/// request. Otherwise `path` is a glob (e.g.
+/// `/api/**`) matched against the request path.
+/// When several rules match, each setting comes
+/// from the first one that has it with rules that
+/// have a `path` checked before ones that don't.
+#[derive(
+  Clone, Debug, Default, Deserialize, PartialEq,
+)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThrottleRule {
+  pub path: Option<String>,
+  /// Milliseconds to wait before answering
+  pub latency: Option<u64>,
+  /// Bytes per second to send responses at
+  pub bandwidth: Option<u64>,
+  /// Fraction of requests (0.0 to 1.0) that fail
+  pub error_rate: Option<f64>,
+  /// Status for failed requests (500 by default)
+  pub error_status: Option<u16>,
+}
+
+impl ThrottleRule {
+  pub fn is_empty(&self) -> bool {
+    self.latency.is_none()
+      && self.bandwidth.is_none()
+      && self.error_rate.is_none()
+  }
+
+  // Fills in anything this rule doesn't set from
+  // another one.
+  fn or(
+    self,
+    other: &ThrottleRule,
+  ) -> ThrottleRule {
+    ThrottleRule {
+      path: None,
+      latency: self.latency.or(other.latency),
+      bandwidth: self.bandwidth.or(other.bandwidth),
+      error_rate: self.error_rate.or(other.error_rate),
+      error_status: self
+        .error_status
+        .or(other.error_status),
+    }
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct ThrottleState {
+  rules: Vec<(Option<GlobMatcher>, ThrottleRule)>,
+}
+
+impl ThrottleState {
+  /// Rules with an invalid glob or error rate are
+  /// logged and skipped.
+  pub fn new(rules: &[ThrottleRule]) -> ThrottleState {
+    let mut rules = rules
+      .iter()
+      .filter_map(|rule| {
+        let valid_rate = rule
+          .error_rate
+          .is_none_or(|rate| (0.0..=1.0).contains(&rate));
+        if !valid_rate {
+          error!(
+            "Throttle error_rate for {} needs to be between 0 and 1",
+            rule_path(rule)
+          );
+          return None;
+        }
+        let matcher = match rule.path.as_deref() {
+          Some(path) => match Glob::new(path) {
+            Ok(glob) => Some(glob.compile_matcher()),
+            Err(e) => {
+              error!("Invalid throttle path {}: {}", path, e);
+              return None;
+            }
+          },
+          None => None,
+        };
+        info!("Throttling {}", rule_path(rule));
+        Some((matcher, rule.clone()))
+      })
+      .collect::<Vec<_>>();
+    rules.sort_by_key(|(matcher, _)| matcher.is_none());
+    ThrottleState { rules }
+  }
+
+  /// The combined settings from every rule that
+  /// matches a path.
+  pub fn settings_for(
+    &self,
+    path: &str,
+  ) -> ThrottleRule {
+    self
+      .rules
+      .iter()
+      .filter(|(matcher, _)| {
+        matcher
+          .as_ref()
+          .is_none_or(|matcher| matcher.is_match(path))
+      })
+      .fold(
+        ThrottleRule::default(),
+        |settings, (_, rule)| settings.or(rule),
+      )
+  }
+}
+
+fn rule_path(rule: &ThrottleRule) -> &str {
+  rule.path.as_deref().unwrap_or("all requests")
+}
+
+/// Waits for the latency, fails the request at
+/// the error rate, then sends the response at the
+/// bandwidth limit. The server's own endpoints
+/// (`/__ssb` and live reload) aren't throttled.
+pub async fn throttle(
+  State(state): State<ThrottleState>,
+  request: Request,
+  next: Next,
+) -> Response {
+  let path = request.uri().path();
+  if is_server_path(path) {
+    return next.run(request).await;
+  }
+  let settings = state.settings_for(path);
+  if let Some(latency) = settings.latency {
+    tokio::time::sleep(Duration::from_millis(latency))
+      .await;
+  }
+  if settings
+    .error_rate
+    .is_some_and(|rate| random_fraction() < rate)
+  {
+    let status = settings
+      .error_status
+      .and_then(|status| {
+        StatusCode::from_u16(status).ok()
+      })
+      .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    return (
+      status,
+      "Error injected by the preview server",
+    )
+      .into_response();
+  }
+  let response = next.run(request).await;
+  match settings.bandwidth {
+    Some(bandwidth) if bandwidth > 0 => response
+      .map(|body| limit_bandwidth(body, bandwidth)),
+    _ => response,
+  }
+}
+
+// Splits the body into tenth of a second chunks
+// and waits after sending each one.
+fn limit_bandwidth(
+  body: Body,
+  bandwidth: u64,
+) -> Body {
+  let chunk_size = (bandwidth / 10).max(1) as usize;
+  let stream = body
+    .into_data_stream()
+    .flat_map(move |data| {
+      stream::iter(match data {
+        Ok(bytes) => chunks(bytes, chunk_size)
+          .into_iter()
+          .map(Ok)
+          .collect(),
+        Err(e) => vec![Err(e)],
+      })
+    })
+    .then(move |chunk| async move {
+      if let Ok(bytes) = &chunk {
+        tokio::time::sleep(Duration::from_secs_f64(
+          bytes.len() as f64 / bandwidth as f64,
+        ))
+        .await;
+      }
+      chunk
+    });
+  Body::from_stream(stream)
+}
+
+fn chunks(
+  bytes: Bytes,
+  chunk_size: usize,
+) -> Vec<Bytes> {
+  (0..bytes.len())
+    .step_by(chunk_size)
+    .map(|start| {
+      bytes.slice(
+        start..(start + chunk_size).min(bytes.len()),
+      )
+    })
+    .collect()
+}
+
+// A number from 0 up to 1. Each `RandomState` has
+// its own random keys so this doesn't need a
+// random number crate.
+fn random_fraction() -> f64 {
+  RandomState::new().build_hasher().finish() as f64
+    / u64::MAX as f64
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use axum::Router;
+  use axum::middleware::from_fn_with_state;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+  use tower::{Layer, ServiceExt};
+
+  fn rules() -> Vec<ThrottleRule> {
+    vec![
+      ThrottleRule {
+        latency: Some(100),
+        ..ThrottleRule::default()
+      },
+      ThrottleRule {
+        path: Some("/api/**".to_string()),
+        latency: Some(500),
+        error_rate: Some(0.05),
+        ..ThrottleRule::default()
+      },
+      ThrottleRule {
+        path: Some("/videos/*".to_string()),
+        bandwidth: Some(50_000),
+        ..ThrottleRule::default()
+      },
+    ]
+  }
+
+  #[rstest]
+  #[case("/", Some(100), None, None)]
+  #[case("/api/users/1", Some(500), None, Some(0.05))]
+  #[case(
+    "/videos/intro.mp4",
+    Some(100),
+    Some(50_000),
+    None
+  )]
+  fn settings_for_test(
+    #[case] path: &str,
+    #[case] latency: Option<u64>,
+    #[case] bandwidth: Option<u64>,
+    #[case] error_rate: Option<f64>,
+  ) {
+    let settings =
+      ThrottleState::new(&rules()).settings_for(path);
+    assert_eq!(
+      (latency, bandwidth, error_rate),
+      (
+        settings.latency,
+        settings.bandwidth,
+        settings.error_rate
+      )
+    );
+  }
+
+  #[test]
+  fn invalid_rules_test() {
+    let state = ThrottleState::new(&[
+      ThrottleRule {
+        path: Some("/api/[".to_string()),
+        latency: Some(100),
+        ..ThrottleRule::default()
+      },
+      ThrottleRule {
+        error_rate: Some(5.0),
+        ..ThrottleRule::default()
+      },
+    ]);
+    assert_eq!(0, state.rules.len());
+  }
+
+  #[rstest]
+  #[case("/tower-livereload/long-poll", StatusCode::OK)]
+  #[case("/__ssb/status", StatusCode::OK)]
+  #[case("/about/", StatusCode::INTERNAL_SERVER_ERROR)]
+  #[tokio::test]
+  async fn throttle_server_paths_test(
+    #[case] path: &str,
+    #[case] expected: StatusCode,
+  ) {
+    let app = from_fn_with_state(
+      ThrottleState::new(&[ThrottleRule {
+        error_rate: Some(1.0),
+        ..ThrottleRule::default()
+      }]),
+      throttle,
+    )
+    .layer(Router::new().fallback(|| async { "ok" }));
+    let response = app
+      .oneshot(
+        Request::builder()
+          .uri(path)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(expected, response.status());
+  }
+
+  #[test]
+  fn chunks_test() {
+    assert_eq!(
+      vec![
+        Bytes::from("alf"),
+        Bytes::from("abr"),
+        Bytes::from("avo")
+      ],
+      chunks(Bytes::from("alfabravo"), 3)
+    );
+  }
+
+  #[test]
+  fn random_fraction_test() {
+    (0..100).for_each(|_| {
+      assert!((0.0..=1.0).contains(&random_fraction()))
+    });
+  }
+}
@@ -0,0 +1,278 @@
+use crate::config::Config;
+use anyhow::{Context, Result, anyhow};
+use axum::serve::Listener;
+use rcgen::generate_simple_self_signed;
+use std::fs;
+use std::io::{self, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{
+  CertificateDer, PrivateKeyDer,
+};
+use tokio_rustls::server::TlsStream;
+use tracing::{debug, error, info};
+
+/// Loads the certificate and key for serving over
+/// HTTPS. User provided files are used if they're
+/// set. Otherwise a self-signed certificate is
+/// generated and cached for next time.
+pub fn tls_acceptor(
+  config: &Config
+) -> Result<TlsAcceptor> {
+  let (cert_path, key_path) =
+    match (&config.tls_cert, &config.tls_key) {
+      (Some(cert), Some(key)) => {
+        (cert.clone(), key.clone())
+      }
+      (None, None) => {
+        let dir = config.certs_dir();
+        self_signed_cert(&dir, &cert_names(config.bind))?;
+        (dir.join("cert.pem"), dir.join("key.pem"))
+      }
+      _ => {
+        return Err(anyhow!(
+          "tls_cert and tls_key need to be set together"
+        ));
+      }
+    };
+  let certs = CertificateDer::pem_file_iter(&cert_path)
+    .and_then(|certs| {
+      certs.collect::<Result<Vec<_>, _>>()
+    })
+    .with_context(|| {
+      format!("Could not read {}", cert_path.display())
+    })?;
+  let key = PrivateKeyDer::from_pem_file(&key_path)
+    .with_context(|| {
+      format!("Could not read {}", key_path.display())
+    })?;
+  let mut server_config =
+    ServerConfig::builder_with_provider(Arc::new(
+      ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)?;
+  server_config.alpn_protocols =
+    vec![b"http/1.1".to_vec()];
+  Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+/// The host names and addresses the generated
+/// certificate covers. When bound to all interfaces
+/// that includes each LAN address.
+pub fn cert_names(bind: IpAddr) -> Vec<String> {
+  let mut names = vec![
+    "localhost".to_string(),
+    "127.0.0.1".to_string(),
+    "::1".to_string(),
+  ];
+  if bind.is_unspecified() {
+    if let Ok(interfaces) = if_addrs::get_if_addrs() {
+      names.extend(
+        interfaces
+          .iter()
+          .filter(|interface| !interface.is_loopback())
+          .map(|interface| interface.ip().to_string()),
+      );
+    }
+  } else if !bind.is_loopback() {
+    names.push(bind.to_string());
+  }
+  names
+}
+
+// The cached certificate is reused as long as it
+// was made for the same names.
+fn self_signed_cert(
+  dir: &Path,
+  names: &[String],
+) -> Result<()> {
+  let names_file = dir.join("names.txt");
+  let names_text = names.join("\n");
+  if dir.join("cert.pem").is_file()
+    && dir.join("key.pem").is_file()
+    && fs::read_to_string(&names_file).ok().as_deref()
+      == Some(names_text.as_str())
+  {
+    return Ok(());
+  }
+  info!(
+    "Generating self-signed certificate in {}",
+    dir.display()
+  );
+  let cert = generate_simple_self_signed(names.to_vec())?;
+  fs::create_dir_all(dir)?;
+  fs::write(dir.join("cert.pem"), cert.cert.pem())?;
+  write_private(
+    &dir.join("key.pem"),
+    &cert.signing_key.serialize_pem(),
+  )?;
+  fs::write(names_file, names_text)?;
+  Ok(())
+}
+
+// Only the owner can read the key on unix.
+fn write_private(
+  path: &Path,
+  contents: &str,
+) -> io::Result<()> {
+  let mut options = fs::OpenOptions::new();
+  options.write(true).create(true).truncate(true);
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::{
+      OpenOptionsExt, PermissionsExt,
+    };
+    options.mode(0o600);
+    // The mode only applies to new files
+    if path.exists() {
+      fs::set_permissions(
+        path,
+        fs::Permissions::from_mode(0o600),
+      )?;
+    }
+  }
+  options.open(path)?.write_all(contents.as_bytes())
+}
+
+/// A listener that hands axum connections once their
+/// TLS handshake is done. Handshakes run in their
+/// own tasks so a slow or rejected one (e.g. a
+/// browser that doesn't trust the certificate yet)
+/// doesn't hold up the others. The TCP listener is
+/// owned here so the port is free as soon as the
+/// server stops.
+pub struct TlsListener {
+  listener: TcpListener,
+  acceptor: TlsAcceptor,
+  tx: mpsc::Sender<(TlsStream<TcpStream>, SocketAddr)>,
+  rx: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+}
+
+impl TlsListener {
+  pub fn new(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+  ) -> TlsListener {
+    let (tx, rx) = mpsc::channel(32);
+    TlsListener {
+      listener,
+      acceptor,
+      tx,
+      rx,
+    }
+  }
+
+  fn handshake(
+    &self,
+    stream: TcpStream,
+    address: SocketAddr,
+  ) {
+    let acceptor = self.acceptor.clone();
+    let tx = self.tx.clone();
+    tokio::spawn(async move {
+      match tokio::time::timeout(
+        Duration::from_secs(10),
+        acceptor.accept(stream),
+      )
+      .await
+      {
+        Ok(Ok(stream)) => {
+          let _ = tx.send((stream, address)).await;
+        }
+        Ok(Err(e)) => {
+          debug!(
+            "TLS handshake with {} failed: {}",
+            address, e
+          )
+        }
+        Err(_) => {
+          debug!(
+            "TLS handshake with {} timed out",
+            address
+          )
+        }
+      }
+    });
+  }
+}
+
+impl Listener for TlsListener {
+  type Io = TlsStream<TcpStream>;
+  type Addr = SocketAddr;
+
+  async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+    loop {
+      tokio::select! {
+        // Never closed since `tx` is kept here
+        Some(connection) = self.rx.recv() => {
+          return connection;
+        }
+        accepted = self.listener.accept() => {
+          match accepted {
+            Ok((stream, address)) => {
+              self.handshake(stream, address)
+            }
+            Err(e) => {
+              error!("Could not accept connection: {}", e);
+              tokio::time::sleep(Duration::from_secs(1))
+                .await;
+            }
+          }
+        }
+      }
+    }
+  }
+
+  fn local_addr(&self) -> io::Result<Self::Addr> {
+    self.listener.local_addr()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("127.0.0.1", vec!["localhost", "127.0.0.1", "::1"])]
+  #[case(
+    "192.168.1.20",
+    vec!["localhost", "127.0.0.1", "::1", "192.168.1.20"]
+  )]
+  fn cert_names_test(
+    #[case] bind: &str,
+    #[case] expected: Vec<&str>,
+  ) {
+    assert_eq!(
+      expected,
+      cert_names(bind.parse().unwrap())
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn self_signed_cert_key_mode_test() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = std::env::temp_dir()
+      .join(format!("ssb-certs-{}", std::process::id()));
+    self_signed_cert(&dir, &["localhost".to_string()])
+      .unwrap();
+    let mode = fs::metadata(dir.join("key.pem"))
+      .unwrap()
+      .permissions()
+      .mode();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(0o600, mode & 0o777);
+  }
+}
@@ -0,0 +1,376 @@
+use crate::mock_api::MockApi;
+use crate::netlify::NetlifyRules;
+use crate::server::access_log::ACCESS_LOG_TARGET;
+use crate::server::forms::NetlifyForm;
+use chrono::{DateTime, Local};
+use minijinja::{Environment, Value};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+/// How many log lines are kept for `/__ssb/logs`
+pub const RECENT_LOG_LINES: usize = 500;
+
+/// The name of the span the builder runs each
+/// build in. Errors logged inside it are the
+/// build's errors.
+pub const BUILD_SPAN: &str = "build";
+
+pub type SharedStatus = Arc<Mutex<BuildStatus>>;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LogLine {
+  pub time: String,
+  pub level: String,
+  pub file: Option<String>,
+  pub line: Option<u32>,
+  pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RouteInfo {
+  pub url: Option<String>,
+  pub source: String,
+  pub output: Option<String>,
+  pub kind: String,
+}
+
+/// Requests for a path that returned 404
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NotFound {
+  pub count: u64,
+  pub last_seen: DateTime<Local>,
+  /// The page that last linked to it
+  pub referer: Option<String>,
+}
+
+/// What the builder is doing and what the last
+/// build produced. It's shared by the builder, the
+/// logger and the preview server.
+#[derive(Debug, Default)]
+pub struct BuildStatus {
+  pub building: bool,
+  pub build_count: u64,
+  pub started: Option<DateTime<Local>>,
+  pub finished: Option<DateTime<Local>>,
+  pub errors: Vec<LogLine>,
+  /// The URL paths of the outputs the last build
+  /// added, removed or changed
+  pub changed_outputs: Vec<String>,
+  pub recent_logs: VecDeque<LogLine>,
+  pub routes: Vec<RouteInfo>,
+  /// The parts of the template context that are
+  /// the same for every page
+  pub site_context: Option<Value>,
+  /// The `file` value for each page keyed by
+  /// its source path
+  pub pages: BTreeMap<String, Value>,
+  /// Paths that returned 404 from the preview
+  /// server
+  pub not_found: BTreeMap<String, NotFound>,
+  /// The Netlify forms in the built site
+  pub forms: Vec<NetlifyForm>,
+  /// The mock API routes from the last build
+  pub mock_api: MockApi,
+  /// What mock API files are rendered with
+  pub mock_env: Option<Arc<Environment<'static>>>,
+  /// The redirects and headers for the built site
+  pub netlify_rules: Arc<NetlifyRules>,
+}
+
+impl BuildStatus {
+  pub fn shared() -> SharedStatus {
+    Arc::new(Mutex::new(BuildStatus::default()))
+  }
+
+  pub fn start_build(&mut self) {
+    self.building = true;
+    self.build_count += 1;
+    self.started = Some(Local::now());
+    self.finished = None;
+    self.errors.clear();
+  }
+
+  pub fn finish_build(&mut self) {
+    self.building = false;
+    self.finished = Some(Local::now());
+  }
+
+  pub fn duration_ms(&self) -> Option<i64> {
+    Some(
+      (self.finished? - self.started?).num_milliseconds(),
+    )
+  }
+
+  pub fn state(&self) -> &'static str {
+    if self.building {
+      "building"
+    } else if self.finished.is_none() {
+      "waiting"
+    } else if self.errors.is_empty() {
+      "ok"
+    } else {
+      "failed"
+    }
+  }
+
+  pub fn add_log_line(
+    &mut self,
+    log_line: LogLine,
+    in_build: bool,
+  ) {
+    if in_build && log_line.level == "ERROR" {
+      self.errors.push(log_line.clone());
+    }
+    self.recent_logs.push_back(log_line);
+    while self.recent_logs.len() > RECENT_LOG_LINES {
+      self.recent_logs.pop_front();
+    }
+  }
+
+  pub fn add_not_found(
+    &mut self,
+    path: &str,
+    referer: Option<String>,
+  ) {
+    let now = Local::now();
+    let not_found = self
+      .not_found
+      .entry(path.to_string())
+      .or_insert(NotFound {
+        count: 0,
+        last_seen: now,
+        referer: None,
+      });
+    not_found.count += 1;
+    not_found.last_seen = now;
+    if referer.is_some() {
+      not_found.referer = referer;
+    }
+  }
+
+  /// The 404s that happened after a time (e.g.
+  /// since the last build started).
+  pub fn not_found_since(
+    &self,
+    since: Option<DateTime<Local>>,
+  ) -> Vec<(String, NotFound)> {
+    self
+      .not_found
+      .iter()
+      .filter(|(_, not_found)| {
+        since.is_none_or(|since| {
+          not_found.last_seen >= since
+        })
+      })
+      .map(|(path, not_found)| {
+        (path.clone(), not_found.clone())
+      })
+      .collect()
+  }
+
+  /// Finds a page by its URL or its source path.
+  pub fn page_key(
+    &self,
+    page: &str,
+  ) -> Option<String> {
+    let page = page.trim_start_matches('/');
+    self
+      .routes
+      .iter()
+      .find(|route| {
+        route.source == page
+          || route.url.as_deref().is_some_and(|url| {
+            url.trim_matches('/')
+              == page.trim_end_matches('/')
+          })
+      })
+      .filter(|route| {
+        self.pages.contains_key(&route.source)
+      })
+      .map(|route| route.source.clone())
+  }
+}
+
+/// A tracing layer that keeps recent log lines
+/// and the errors from the current build in the
+/// shared status.
+pub struct StatusLayer {
+  status: SharedStatus,
+}
+
+impl StatusLayer {
+  pub fn new(status: SharedStatus) -> StatusLayer {
+    StatusLayer { status }
+  }
+}
+
+impl<S> Layer<S> for StatusLayer
+where
+  S: Subscriber + for<'a> LookupSpan<'a>,
+{
+  fn on_event(
+    &self,
+    event: &Event<'_>,
+    ctx: Context<'_, S>,
+  ) {
+    let meta = event.metadata();
+    // Requests are left out unless they're 404s
+    // so they don't push out the build's logs.
+    let max_level = if meta.target() == ACCESS_LOG_TARGET
+    {
+      Level::WARN
+    } else {
+      Level::INFO
+    };
+    if *meta.level() > max_level {
+      return;
+    }
+    let in_build =
+      ctx.event_scope(event).is_some_and(|mut scope| {
+        scope.any(|span| span.name() == BUILD_SPAN)
+      });
+    let mut visitor = MessageVisitor::default();
+    event.record(&mut visitor);
+    let log_line = LogLine {
+      time: Local::now().to_rfc3339(),
+      level: meta.level().to_string(),
+      file: meta.file().map(|file| file.to_string()),
+      line: meta.line(),
+      message: visitor.message,
+    };
+    if let Ok(mut status) = self.status.lock() {
+      status.add_log_line(log_line, in_build);
+    }
+  }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+  message: String,
+}
+
+impl Visit for MessageVisitor {
+  fn record_debug(
+    &mut self,
+    field: &Field,
+    value: &dyn Debug,
+  ) {
+    if !self.message.is_empty() {
+      self.message.push(' ');
+    }
+    if field.name() == "message" {
+      self.message.push_str(&format!("{:?}", value));
+    } else {
+      self.message.push_str(&format!(
+        "{}={:?}",
+        field.name(),
+        value
+      ));
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn log_line(level: &str) -> LogLine {
+    LogLine {
+      time: "".to_string(),
+      level: level.to_string(),
+      file: None,
+      line: None,
+      message: "alfa".to_string(),
+    }
+  }
+
+  #[test]
+  fn build_errors_test() {
+    let mut status = BuildStatus::default();
+    assert_eq!("waiting", status.state());
+    status.start_build();
+    status.add_log_line(log_line("INFO"), true);
+    status.add_log_line(log_line("ERROR"), false);
+    assert_eq!("building", status.state());
+    status.finish_build();
+    assert_eq!("ok", status.state());
+    status.start_build();
+    status.add_log_line(log_line("ERROR"), true);
+    status.finish_build();
+    assert_eq!("failed", status.state());
+    assert_eq!(1, status.errors.len());
+    assert_eq!(3, status.recent_logs.len());
+  }
+
+  #[test]
+  fn recent_logs_limit_test() {
+    let mut status = BuildStatus::default();
+    (0..RECENT_LOG_LINES + 10).for_each(|_| {
+      status.add_log_line(log_line("INFO"), false)
+    });
+    assert_eq!(
+      RECENT_LOG_LINES,
+      status.recent_logs.len()
+    );
+  }
+
+  #[test]
+  fn not_found_test() {
+    let mut status = BuildStatus::default();
+    status.add_not_found("/alfa.css", None);
+    let since = Local::now();
+    status.add_not_found(
+      "/bravo.js",
+      Some("http://localhost:5444/".to_string()),
+    );
+    status.add_not_found("/bravo.js", None);
+    assert_eq!(2, status.not_found_since(None).len());
+    let recent = status.not_found_since(Some(since));
+    assert_eq!(1, recent.len());
+    assert_eq!("/bravo.js", recent[0].0);
+    assert_eq!(2, recent[0].1.count);
+    assert_eq!(
+      Some("http://localhost:5444/".to_string()),
+      recent[0].1.referer
+    );
+  }
+
+  #[rstest]
+  #[case("/", Some("index.html"))]
+  #[case("/about/", Some("about.html"))]
+  #[case("/about", Some("about.html"))]
+  #[case("about.html", Some("about.html"))]
+  #[case("/missing/", None)]
+  fn page_key_test(
+    #[case] page: &str,
+    #[case] expected: Option<&str>,
+  ) {
+    let mut status = BuildStatus::default();
+    for (source, url) in
+      [("index.html", "/"), ("about.html", "/about/")]
+    {
+      status.routes.push(RouteInfo {
+        url: Some(url.to_string()),
+        source: source.to_string(),
+        output: None,
+        kind: "TransformHtml".to_string(),
+      });
+      status
+        .pages
+        .insert(source.to_string(), Value::from(()));
+    }
+    assert_eq!(
+      expected.map(|key| key.to_string()),
+      status.page_key(page)
+    );
+  }
+}
@@ -1,41 +1,269 @@
 use crate::Config;
+use crate::builder::Rebuild;
+use crate::builder::utils::IgnoreRules;
 use anyhow::Result;
-use chrono::{DateTime, Local};
 use itertools::Itertools;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::mpsc::Sender;
-use tracing::info;
-use watchexec::Watchexec;
+use tokio::task::AbortHandle;
+use tracing::{info, warn};
+use watchexec::error::{
+  CriticalError, FsWatcherError, RuntimeError,
+};
+use watchexec::sources::fs::Watcher as Backend;
+use watchexec::{ErrorHook, WatchedPath, Watchexec};
 use watchexec_events::filekind::*;
 use watchexec_events::{Event, Tag};
 use watchexec_signals::Signal;
 
+/// What a changed file means for the site.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Change {
+  /// The site needs to be rebuilt
+  Site,
+  /// The config file needs to be loaded again
+  Config,
+}
+
+/// Milliseconds between checks when polling and
+/// no interval is given
+pub const DEFAULT_POLL_INTERVAL: u64 = 500;
+
 pub struct Watcher {
-  config: Config,
-  tx: Sender<DateTime<Local>>,
+  config_file: PathBuf,
+  roots: Arc<Mutex<Vec<PathBuf>>>,
+  ignore: Arc<Mutex<IgnoreRules>>,
+  tx: Sender<Rebuild>,
+  config_tx: Sender<()>,
+  wx: Mutex<Arc<Watchexec>>,
+  pathset: Mutex<Vec<WatchedPath>>,
+  backend: Arc<Mutex<Backend>>,
+  native_failed: Arc<AtomicBool>,
+  running: Arc<Mutex<Option<AbortHandle>>>,
 }
 
 impl Watcher {
   pub fn new(
-    config: Config,
-    tx: Sender<DateTime<Local>>,
-  ) -> Watcher {
-    Watcher { config, tx }
+    config: &Config,
+    config_file: &Path,
+    tx: Sender<Rebuild>,
+    config_tx: Sender<()>,
+  ) -> Result<Watcher> {
+    let watcher = Watcher {
+      config_file: std::path::absolute(config_file)?,
+      roots: Arc::new(Mutex::new(vec![])),
+      // Loaded by `watch`
+      ignore: Arc::new(
+        Mutex::new(IgnoreRules::default()),
+      ),
+      tx,
+      config_tx,
+      wx: Mutex::new(Arc::new(Watchexec::default())),
+      pathset: Mutex::new(vec![]),
+      backend: Arc::new(Mutex::new(backend_for(
+        config.poll,
+        false,
+      ))),
+      native_failed: Arc::new(AtomicBool::new(false)),
+      running: Arc::new(Mutex::new(None)),
+    };
+    watcher.watch(config);
+    Ok(watcher)
   }
+
+  /// Points the watcher at the folders and files
+  /// from a config. It can be called again when
+  /// the config changes. The folders of the config
+  /// file and `netlify.toml` are watched (without
+  /// their subfolders) so the files are still seen
+  /// when an editor replaces them instead of
+  /// writing to them.
+  pub fn watch(
+    &self,
+    config: &Config,
+  ) {
+    let roots = config
+      .watch_roots()
+      .iter()
+      .filter_map(|root| {
+        if root.exists() {
+          std::path::absolute(root).ok()
+        } else {
+          warn!(
+            "Not watching {}: it doesn't exist",
+            root.display()
+          );
+          None
+        }
+      })
+      .collect::<Vec<_>>();
+    roots.iter().for_each(|root| {
+      info!("Watching {}", root.display())
+    });
+    let mut pathset = roots
+      .iter()
+      .map(WatchedPath::recursive)
+      .collect::<Vec<_>>();
+    // The build loads the Netlify rules but
+    // `netlify.toml` is usually outside the roots.
+    let files = std::path::absolute(&config.netlify_toml)
+      .into_iter()
+      .collect::<Vec<_>>();
+    files
+      .iter()
+      .filter_map(|file| file.parent())
+      .chain(self.config_file.parent())
+      .map(WatchedPath::non_recursive)
+      .for_each(|path| {
+        if !pathset.contains(&path) {
+          pathset.push(path);
+        }
+      });
+    *self.pathset.lock().unwrap() = pathset.clone();
+    *self.roots.lock().unwrap() =
+      roots.into_iter().chain(files).collect();
+    *self.ignore.lock().unwrap() =
+      IgnoreRules::new(config);
+    self.wx.lock().unwrap().config.pathset(pathset);
+    switch_backend(
+      backend_for(
+        config.poll,
+        self.native_failed.load(Ordering::Relaxed),
+      ),
+      &self.backend,
+      &self.running,
+    );
+  }
+
+  /// Runs until Ctrl+C. If native file watching
+  /// can't start (e.g. on some mounted volumes) it
+  /// starts again with polling. Switching backends
+  /// also starts a new runtime since a running one
+  /// keeps watching with the backend it started
+  /// with.
   pub async fn start(&self) -> Result<()> {
     info!("Starting watcher");
-    let wx = Watchexec::default();
-    wx.config.pathset(vec![
-      self.config.content_root.display().to_string(),
-    ]);
+    loop {
+      let wx = self.wx.lock().unwrap().clone();
+      let backend = *self.backend.lock().unwrap();
+      info!("Watching with {}", backend_name(backend));
+      self.configure(&wx);
+      let main = wx.main();
+      *self.running.lock().unwrap() =
+        Some(main.abort_handle());
+      match main.await {
+        // Stopped by `switch_backend`
+        Err(e) if e.is_cancelled() => {}
+        Ok(Err(CriticalError::FsWatcherInit {
+          kind: Backend::Native,
+          err,
+        })) => {
+          warn!(
+            "Native file watching failed ({}). Falling back to polling",
+            err
+          );
+          self
+            .native_failed
+            .store(true, Ordering::Relaxed);
+          *self.backend.lock().unwrap() =
+            backend_for(None, true);
+        }
+        result => {
+          result??;
+          break;
+        }
+      }
+      *self.wx.lock().unwrap() =
+        Arc::new(Watchexec::default());
+    }
+    println!("Watcher stopped.");
+    Ok(())
+  }
+
+  // Sets up a Watchexec runtime before it starts.
+  fn configure(
+    &self,
+    wx: &Watchexec,
+  ) {
+    wx.config
+      .pathset(self.pathset.lock().unwrap().clone());
+    wx.config.file_watcher(*self.backend.lock().unwrap());
+    let backend = self.backend.clone();
+    let native_failed = self.native_failed.clone();
+    let running = self.running.clone();
+    // Native watching can also fail once it's
+    // running (e.g. when it runs out of watches).
+    wx.config.on_error(move |hook: ErrorHook| {
+      if let RuntimeError::FsWatcher {
+        kind: Backend::Native,
+        err:
+          FsWatcherError::Create(_)
+          | FsWatcherError::TooManyWatches(_)
+          | FsWatcherError::TooManyHandles(_),
+      } = &hook.error
+      {
+        warn!(
+          "Native file watching failed ({}). Falling back to polling",
+          hook.error
+        );
+        native_failed.store(true, Ordering::Relaxed);
+        switch_backend(
+          backend_for(None, true),
+          &backend,
+          &running,
+        );
+      } else {
+        warn!("Watcher: {}", hook.error);
+      }
+    });
+    let config_file = self.config_file.clone();
+    let roots = self.roots.clone();
+    let ignore = self.ignore.clone();
     let tx2 = self.tx.clone();
+    let config_tx = self.config_tx.clone();
     wx.config.on_action(move |mut action| {
       let paths = filter_paths(&action.events);
-      if !paths.is_empty() {
+      // Checked before the rules are applied since
+      // ignore files are ignored themselves.
+      let rules_changed = paths
+        .iter()
+        .any(|path| IgnoreRules::is_ignore_file(path));
+      if rules_changed {
+        let mut ignore = ignore.lock().unwrap();
+        *ignore = ignore.reload();
+      }
+      let changes = {
+        let ignore = ignore.lock().unwrap();
+        paths
+          .iter()
+          .filter(|path| !ignore.is_ignored(path))
+          .filter_map(|path| {
+            change_for(
+              path,
+              &config_file,
+              &roots.lock().unwrap(),
+            )
+          })
+          .collect::<Vec<_>>()
+      };
+      if changes.contains(&Change::Config) {
+        let config_tx = config_tx.clone();
+        tokio::spawn(async move {
+          config_tx.send(()).await.unwrap();
+        });
+      } else if rules_changed
+        || changes.contains(&Change::Site)
+      {
         let tx3 = tx2.clone();
         tokio::spawn(async move {
-          tx3.send(chrono::Local::now()).await.unwrap();
+          tx3
+            .send(Rebuild::Changed(chrono::Local::now()))
+            .await
+            .unwrap();
         });
       }
       if action.signals().any(|sig| {
@@ -47,9 +275,73 @@ impl Watcher {
       }
       action
     });
-    let _ = wx.main().await?;
-    println!("Watcher stopped.");
-    Ok(())
+  }
+}
+
+/// Stops the running runtime when the backend
+/// changes so `start` begins a new one with it.
+fn switch_backend(
+  backend: Backend,
+  current: &Mutex<Backend>,
+  running: &Mutex<Option<AbortHandle>>,
+) {
+  let changed = {
+    let mut current = current.lock().unwrap();
+    let changed = *current != backend;
+    *current = backend;
+    changed
+  };
+  if changed {
+    info!("Switching to {}", backend_name(backend));
+    if let Some(running) = running.lock().unwrap().take()
+    {
+      running.abort();
+    }
+  }
+}
+
+/// Polls when an interval is set. Once native
+/// watching has failed it's not tried again.
+pub fn backend_for(
+  poll: Option<NonZeroU64>,
+  native_failed: bool,
+) -> Backend {
+  match poll {
+    Some(interval) => {
+      Backend::Poll(Duration::from_millis(interval.get()))
+    }
+    None if native_failed => Backend::Poll(
+      Duration::from_millis(DEFAULT_POLL_INTERVAL),
+    ),
+    None => Backend::Native,
+  }
+}
+
+fn backend_name(backend: Backend) -> String {
+  match backend {
+    Backend::Poll(interval) => {
+      format!("polling every {}ms", interval.as_millis())
+    }
+    _ => "native file notifications".to_string(),
+  }
+}
+
+/// Sorts out what a changed path is. Other files
+/// next to the config file are ignored.
+pub fn change_for(
+  path: &Path,
+  config_file: &Path,
+  roots: &[PathBuf],
+) -> Option<Change> {
+  if path == config_file {
+    Some(Change::Config)
+  } else if roots
+    .iter()
+    .any(|root| path.starts_with(root))
+  {
+    Some(Change::Site)
+  } else {
+    None
   }
 }
 
@@ -68,32 +360,31 @@ fn filter_paths(events: &Arc<[Event]>) -> Vec<PathBuf> {
           Tag::FileEventKind(FileEventKind::Create(
             CreateKind::File
           ),)
+        ) || matches!(
+          // What the polling backend reports when a
+          // file's modified time or content changes
+          // or a new one turns up
+          tag,
+          Tag::FileEventKind(
+            FileEventKind::Modify(
+              ModifyKind::Metadata(
+                MetadataKind::WriteTime
+              ) | ModifyKind::Data(DataChange::Any)
+            ) | FileEventKind::Create(CreateKind::Any)
+          )
+        ) || matches!(
+          // Editors that save by renaming a
+          // temporary file over the original
+          tag,
+          Tag::FileEventKind(FileEventKind::Modify(
+            ModifyKind::Name(RenameMode::To)
+          ),)
         )
       })
     })
     .filter_map(|event| {
       event.tags.iter().find_map(|tag| {
         if let Tag::Path { path, .. } = tag {
-          for component in path.components() {
-            if let std::path::Component::Normal(part) =
-              component
-            {
-              if part
-                .display()
-                .to_string()
-                .starts_with(".")
-              {
-                return None;
-              }
-            }
-          }
-          if let Some(file_name_path) = path.file_name() {
-            let file_name =
-              file_name_path.display().to_string();
-            if file_name.ends_with("~") {
-              return None;
-            }
-          };
           Some(path.to_path_buf())
         } else {
           None
@@ -103,3 +394,57 @@ fn filter_paths(events: &Arc<[Event]>) -> Vec<PathBuf> {
     .unique()
     .collect()
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case(None, false, Backend::Native)]
+  #[case(
+    None,
+    true,
+    Backend::Poll(Duration::from_millis(500))
+  )]
+  #[case(
+    NonZeroU64::new(2000),
+    false,
+    Backend::Poll(Duration::from_secs(2))
+  )]
+  fn backend_for_test(
+    #[case] poll: Option<NonZeroU64>,
+    #[case] native_failed: bool,
+    #[case] expected: Backend,
+  ) {
+    assert_eq!(
+      expected,
+      backend_for(poll, native_failed)
+    );
+  }
+
+  #[rstest]
+  #[case("/site/ssb.toml", Some(Change::Config))]
+  #[case("/site/content/index.html", Some(Change::Site))]
+  #[case("/shared/theme/base.html", Some(Change::Site))]
+  #[case("/site/README.md", None)]
+  #[case("/site/docs/index.html", None)]
+  fn change_for_test(
+    #[case] path: &str,
+    #[case] expected: Option<Change>,
+  ) {
+    let roots = vec![
+      PathBuf::from("/site/content"),
+      PathBuf::from("/shared/theme"),
+    ];
+    assert_eq!(
+      expected,
+      change_for(
+        Path::new(path),
+        Path::new("/site/ssb.toml"),
+        &roots
+      )
+    );
+  }
+}